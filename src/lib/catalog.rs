@@ -0,0 +1,262 @@
+use std::io::Read;
+use std::fs::File;
+use std::error::Error;
+
+use tcod::colors::Color;
+
+use super::*;
+
+/// Everything the game spawns that is described by data files rather than code.
+#[derive(Clone, Debug)]
+pub struct Catalog {
+    pub monsters: Vec<MonsterDef>,
+    pub items: Vec<ItemDef>,
+}
+
+/// One kind of monster, as described in `data/monsters.json`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MonsterDef {
+    pub name: String,
+    pub glyph: char,
+    pub color: Color,
+    pub hp: i32,
+    pub defense: i32,
+    pub power: i32,
+    pub xp: i32,
+    /// NORMAL_SPEED acts once per player turn, twice that acts twice
+    #[serde(default = "normal_speed")]
+    pub speed: i32,
+    /// how many tiles away it notices the player
+    #[serde(default = "monster_sight")]
+    pub sight: i32,
+    /// rolled for the damage of every blow, on top of power
+    #[serde(default)]
+    pub damage: Dice,
+    /// better or worse chance to hit, in percent
+    #[serde(default)]
+    pub accuracy: i32,
+    /// better or worse chance to be missed, in percent
+    #[serde(default)]
+    pub evasion: i32,
+    pub ai: Ai,
+    /// how likely this monster is to be picked, by dungeon level
+    pub spawn: Vec<Transition>,
+}
+
+/// One kind of item, as described in `data/items.json`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ItemDef {
+    pub name: String,
+    pub glyph: char,
+    pub color: Color,
+    pub effect: Item,
+    /// slot and bonuses, for items whose effect is `Equip`
+    #[serde(default)]
+    pub equipment: Option<Equipment>,
+    /// how likely this item is to be picked, by dungeon level
+    pub spawn: Vec<Transition>,
+    /// whether the player starts the game carrying one
+    #[serde(default)]
+    pub starting: bool,
+    /// how many are found together, for ammunition
+    #[serde(default = "one")]
+    pub count: u32,
+    /// how heavy one is, which is also the damage it does when thrown
+    #[serde(default)]
+    pub weight: i32,
+}
+
+impl Catalog {
+    /// Load and check all the data files, describing the first problem found
+    pub fn load() -> Result<Self, Box<dyn Error>> {
+        let monsters: Vec<MonsterDef> = read_json("data/monsters.json")?;
+        validate_monsters(&monsters)
+            .map_err(|e| format!("data/monsters.json: {}", e))?;
+        let items: Vec<ItemDef> = read_json("data/items.json")?;
+        validate_items(&items)
+            .map_err(|e| format!("data/items.json: {}", e))?;
+        Ok(Catalog { monsters, items })
+    }
+}
+
+impl MonsterDef {
+    /// Create a living monster of this kind
+    pub fn spawn(&self, x: i32, y: i32) -> Object {
+        let mut monster = Object::new(x, y, self.glyph, self.color, &self.name, true);
+        monster.fighter = Some(Fighter {
+            base_max_hp:     self.hp,
+            hp:         self.hp,
+            base_defense:    self.defense,
+            base_power:      self.power,
+            on_death:   DeathCallback::Monster,
+            xp:         self.xp,
+            base_speed: self.speed,
+            energy:     0,
+            sight:      self.sight,
+            damage:     self.damage,
+            accuracy:   self.accuracy,
+            evasion:    self.evasion,
+        });
+        monster.ai = Some(self.ai.clone());
+        monster.alive = true;
+        monster
+    }
+}
+
+impl ItemDef {
+    /// Create an item of this kind lying on the floor
+    pub fn spawn(&self, x: i32, y: i32) -> Object {
+        let mut item = Object::new(x, y, self.glyph, self.color, &self.name, false);
+        item.item = Some(self.effect.clone());
+        item.count = self.count;
+        item.weight = self.weight;
+        item.equipment = self.equipment.map(|equipment| Equipment {
+            equipped: false,
+            ..equipment
+        });
+        item
+    }
+}
+
+fn one() -> u32 {
+    1
+}
+
+fn normal_speed() -> i32 {
+    NORMAL_SPEED
+}
+
+fn monster_sight() -> i32 {
+    MONSTER_SIGHT_RADIUS
+}
+
+fn read_json<T>(path: &str) -> Result<T, Box<dyn Error>>
+where
+    T: serde::de::DeserializeOwned,
+{
+    let mut contents = String::new();
+    File::open(path)
+        .and_then(|mut file| file.read_to_string(&mut contents))
+        .map_err(|e| format!("{}: {}", path, e))?;
+    let value = serde_json::from_str(&contents).map_err(|e| format!("{}: {}", path, e))?;
+    Ok(value)
+}
+
+fn validate_monsters(monsters: &[MonsterDef]) -> Result<(), String> {
+    if monsters.is_empty() {
+        return Err("no monsters are defined".into());
+    }
+    for (index, monster) in monsters.iter().enumerate() {
+        let fail = |problem: &str| Err(format!("monster {:?}: {}", monster.name, problem));
+        if monster.name.is_empty() {
+            return Err(format!("monster #{} has no name", index + 1));
+        }
+        if monsters[..index].iter().any(|other| other.name == monster.name) {
+            return fail("defined more than once");
+        }
+        if monster.hp <= 0 {
+            return fail("hp must be positive");
+        }
+        if monster.defense < 0 || monster.power < 0 || monster.xp < 0 {
+            return fail("defense, power and xp cannot be negative");
+        }
+        if monster.speed <= 0 {
+            return fail("speed must be positive");
+        }
+        if monster.sight < 0 {
+            return fail("sight cannot be negative");
+        }
+        if let Ai::Archer(weapon) = monster.ai {
+            if weapon.range <= 0 {
+                return fail("an archer needs a positive range");
+            }
+        }
+        validate_transitions(&monster.spawn).or_else(|e| fail(&format!("spawn: {}", e)))?;
+    }
+    Ok(())
+}
+
+fn validate_items(items: &[ItemDef]) -> Result<(), String> {
+    // levels lock doors with the key left somewhere on the near side
+    if !items.iter().any(|item| item.effect == Item::Key) {
+        return Err("no Key item is defined to open locked doors".into());
+    }
+    for (index, item) in items.iter().enumerate() {
+        let fail = |problem: &str| Err(format!("item {:?}: {}", item.name, problem));
+        if item.name.is_empty() {
+            return Err(format!("item #{} has no name", index + 1));
+        }
+        if items[..index].iter().any(|other| other.name == item.name) {
+            return fail("defined more than once");
+        }
+        if let Item::Magic { aim, effects, shatters } = &item.effect {
+            if effects.is_empty() {
+                return fail("magic needs at least one effect");
+            }
+            let valid_aim = match *aim {
+                Aim::Myself => true,
+                Aim::Bolt { range } => range > 0,
+                Aim::Ball { radius } => radius >= 0,
+            };
+            if !valid_aim || !effects.iter().all(|effect| effect.is_valid()) {
+                return fail("effect amounts, damage, ranges and durations must be positive");
+            }
+            if *shatters && *aim != Aim::Myself {
+                return fail("only magic aimed at yourself can shatter");
+            }
+        }
+        match (&item.effect, item.equipment) {
+            (Item::Equip, None) => return fail("an Equip item needs an equipment section"),
+            (Item::Equip, Some(equipment)) if equipment.light.is_some_and(|light| light.radius <= 0) => {
+                return fail("a light needs a positive radius")
+            }
+            (Item::Equip, Some(Equipment { ranged: Some(ranged), .. }))
+                if ranged.range <= 0 =>
+            {
+                return fail("a launcher needs a positive range")
+            }
+            (Item::Equip, Some(_)) => {}
+            (_, Some(_)) => return fail("only Equip items can have an equipment section"),
+            (_, None) => {}
+        }
+        if item.weight < 0 {
+            return fail("weight cannot be negative");
+        }
+        match &item.effect {
+            _ if item.count == 0 => return fail("count must be positive"),
+            Item::Ammo { .. } => {}
+            _ if item.count > 1 => return fail("only Ammo items can come more than one at a time"),
+            _ => {}
+        }
+        validate_transitions(&item.spawn).or_else(|e| fail(&format!("spawn: {}", e)))?;
+    }
+    Ok(())
+}
+
+/// Transition tables must be in increasing level order, starting at level 1 or deeper
+fn validate_transitions(table: &[Transition]) -> Result<(), String> {
+    let mut previous_level = 0;
+    for transition in table {
+        if transition.level <= previous_level {
+            return Err(format!(
+                "levels must start at 1 and increase, found {} after {}",
+                transition.level, previous_level
+            ));
+        }
+        previous_level = transition.level;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn items_need_a_key_for_locked_doors() {
+        let mut items = Catalog::load().unwrap().items;
+        assert!(validate_items(&items).is_ok());
+        items.retain(|item| item.effect != Item::Key);
+        assert!(validate_items(&items).is_err());
+    }
+}
@@ -0,0 +1,225 @@
+use std::fmt;
+
+use rand::Rng;
+use serde::de::{self, Deserialize, Deserializer};
+use serde::ser::{Serialize, Serializer};
+
+use super::*;
+
+/// A damage roll like "2d4+1": `count` dice with `sides` sides each, plus
+/// `bonus`. Written the same way in the data files.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Dice {
+    pub count: i32,
+    pub sides: i32,
+    pub bonus: i32,
+}
+
+impl Dice {
+    /// Always the same amount, with no dice to roll
+    pub fn flat(bonus: i32) -> Self {
+        Dice { count: 0, sides: 0, bonus }
+    }
+
+    pub fn roll<R: Rng>(&self, rng: &mut R) -> i32 {
+        let rolled: i32 = (0..self.count).map(|_| rng.gen_range(1, self.sides + 1)).sum();
+        rolled + self.bonus
+    }
+
+    /// Read a roll like "2d4+1", "1d6-1", "3d8" or a flat "5"
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let invalid = || format!("{:?} is not a dice roll like \"2d4+1\"", text);
+        let text = text.trim();
+        let (dice, bonus) = match text.find(&['+', '-'][..]) {
+            Some(0) | None => (text, ""),
+            Some(at) => text.split_at(at),
+        };
+        // a single sign, then a number
+        let bonus = match bonus {
+            "" => 0,
+            bonus => {
+                let number = bonus.strip_prefix(&['+', '-'][..]).unwrap_or(bonus);
+                if number.is_empty() || !number.chars().all(|c| c.is_ascii_digit()) {
+                    return Err(invalid());
+                }
+                bonus.parse().map_err(|_| invalid())?
+            }
+        };
+        match dice.find('d') {
+            Some(at) => {
+                let count = dice[..at].parse().map_err(|_| invalid())?;
+                let sides = dice[at + 1..].parse().map_err(|_| invalid())?;
+                if count < 1 || sides < 1 {
+                    return Err(invalid());
+                }
+                Ok(Dice { count, sides, bonus })
+            }
+            None if bonus == 0 => dice.parse().map(Dice::flat).map_err(|_| invalid()),
+            None => Err(invalid()),
+        }
+    }
+}
+
+impl fmt::Display for Dice {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.count == 0 {
+            return write!(f, "{}", self.bonus);
+        }
+        write!(f, "{}d{}", self.count, self.sides)?;
+        if self.bonus != 0 {
+            write!(f, "{:+}", self.bonus)?;
+        }
+        Ok(())
+    }
+}
+
+impl Serialize for Dice {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for Dice {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        Dice::parse(&text).map_err(de::Error::custom)
+    }
+}
+
+/// What an attacker brings to a blow
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Strike {
+    /// added to the chance to hit, in percent
+    pub accuracy: i32,
+    /// added to every damage roll
+    pub power: i32,
+    pub damage: Dice,
+}
+
+/// What the one being attacked has against it
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Guard {
+    /// taken off the chance to hit, in percent
+    pub evasion: i32,
+    /// taken off the damage of every hit that isn't critical
+    pub defense: i32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Outcome {
+    Miss,
+    Hit { damage: i32, critical: bool },
+}
+
+/// How a single attack turns out. Every attack in the game, up close or
+/// from afar, is settled here.
+///
+/// It hits BASE_HIT_PERCENT of the time, plus the attacker's accuracy, less
+/// the defender's evasion, but never less than MIN_HIT_PERCENT or more than
+/// MAX_HIT_PERCENT of the time. A hit does the damage roll plus power, less
+/// defense. CRIT_PERCENT out of every 100 hits are critical: the dice are
+/// rolled twice and defense doesn't count. Any hit does at least MIN_DAMAGE.
+pub fn resolve_attack<R: Rng>(strike: Strike, guard: Guard, rng: &mut R) -> Outcome {
+    let chance = BASE_HIT_PERCENT + strike.accuracy - guard.evasion;
+    let chance = cmp::min(MAX_HIT_PERCENT, cmp::max(MIN_HIT_PERCENT, chance));
+    if rng.gen_range(0, 100) >= chance {
+        return Outcome::Miss;
+    }
+    let critical = rng.gen_range(0, 100) < CRIT_PERCENT;
+    let damage = if critical {
+        strike.damage.roll(rng) + strike.damage.roll(rng) + strike.power
+    } else {
+        strike.damage.roll(rng) + strike.power - guard.defense
+    };
+    Outcome::Hit {
+        damage: cmp::max(MIN_DAMAGE, damage),
+        critical,
+    }
+}
+
+impl Object {
+    /// A blow with whatever it fights with up close
+    pub fn melee_strike(&self, game: &Game) -> Strike {
+        Strike {
+            accuracy: self.fighter.map_or(0, |f| f.accuracy),
+            power: self.power(game),
+            damage: self.fighter.map_or_else(Dice::default, |f| f.damage),
+        }
+    }
+
+    /// A shot or throw doing `damage`, aimed as well as this object can
+    pub fn ranged_strike(&self, damage: Dice) -> Strike {
+        Strike {
+            accuracy: self.fighter.map_or(0, |f| f.accuracy),
+            power: 0,
+            damage,
+        }
+    }
+
+    pub fn guard(&self, game: &Game) -> Guard {
+        Guard {
+            evasion: self.fighter.map_or(0, |f| f.evasion),
+            defense: self.defense(game),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn attacks(strike: Strike, guard: Guard) -> Vec<Outcome> {
+        let mut rng = GameRng::new(7);
+        (0..2000).map(|_| resolve_attack(strike, guard, &mut rng)).collect()
+    }
+
+    fn misses(outcomes: &[Outcome]) -> usize {
+        outcomes.iter().filter(|&&outcome| outcome == Outcome::Miss).count()
+    }
+
+    #[test]
+    fn parses_dice() {
+        assert_eq!(Dice::parse("2d4+1"), Ok(Dice { count: 2, sides: 4, bonus: 1 }));
+        assert_eq!(Dice::parse("1d6-1"), Ok(Dice { count: 1, sides: 6, bonus: -1 }));
+        assert_eq!(Dice::parse("5"), Ok(Dice::flat(5)));
+        assert!(Dice::parse("d6").is_err());
+        assert!(Dice::parse("1d6+").is_err());
+        assert!(Dice::parse("1d6-").is_err());
+        assert!(Dice::parse("1d6++2").is_err());
+        assert!(Dice::parse("1d6+-2").is_err());
+    }
+
+    #[test]
+    fn hit_chance_is_clamped() {
+        let damage = Dice::flat(1);
+        let sure = Strike { accuracy: 1000, power: 0, damage };
+        let hopeless = Strike { accuracy: -1000, power: 0, damage };
+        let guard = Guard { evasion: 0, defense: 0 };
+
+        let missed = misses(&attacks(sure, guard));
+        let expected = 2000 * (100 - MAX_HIT_PERCENT) as usize / 100;
+        assert!(missed > expected / 2 && missed < expected * 2);
+
+        let hit = 2000 - misses(&attacks(hopeless, guard));
+        let expected = 2000 * MIN_HIT_PERCENT as usize / 100;
+        assert!(hit > expected / 2 && hit < expected * 2);
+    }
+
+    #[test]
+    fn criticals_ignore_defense_and_hits_do_at_least_min_damage() {
+        let strike = Strike { accuracy: 1000, power: 0, damage: Dice::flat(3) };
+        let guard = Guard { evasion: 0, defense: 1000 };
+        let mut criticals = 0;
+        for outcome in attacks(strike, guard) {
+            match outcome {
+                Outcome::Hit { damage, critical: true } => {
+                    assert_eq!(damage, 6);
+                    criticals += 1;
+                }
+                Outcome::Hit { damage, critical: false } => assert_eq!(damage, MIN_DAMAGE),
+                Outcome::Miss => {}
+            }
+        }
+        assert!(criticals > 0);
+    }
+}
@@ -13,6 +13,8 @@ pub static PANEL_Y: i32 = SCREEN_HEIGHT - PANEL_HEIGHT;
 pub static MSG_X:     i32 = BAR_WIDTH + 2;
 pub static MSG_WIDTH: i32 = SCREEN_WIDTH - BAR_WIDTH -2;
 pub static MSG_HEIGHT: usize = PANEL_HEIGHT as usize -1;
+// how many messages a log keeps, in the game and in the save, unless told otherwise
+pub static MESSAGE_HISTORY_LIMIT: usize = 500;
 
 pub static MAP_WIDTH: i32 = 80;
 pub static MAP_HEIGHT: i32 = 45;
@@ -25,28 +27,101 @@ pub static COLOR_LIGHT_GROUND: Color = Color { r: 200, g: 180, b: 50 };
 pub static ROOM_MAX_SIZE: i32 = 10;
 pub static ROOM_MIN_SIZE: i32 = 6;
 pub static MAX_ROOMS: i32 = 30;
+// how many times the BSP generator splits the map, and the smallest area a
+// split may leave
+pub static BSP_DEPTH: u32 = 4;
+pub static BSP_MIN_AREA: i32 = 8;
+// how much of a fresh cave starts out as wall, how often it is smoothed, how
+// much of the map the cave must cover to be kept, and how big a patch of it
+// gets its own monsters and items
+pub static CAVE_WALL_PERCENT: u32 = 45;
+pub static CAVE_SMOOTHING_STEPS: u32 = 5;
+pub static CAVE_MIN_OPEN_PERCENT: i32 = 35;
+pub static CAVE_AREA_SIZE: i32 = 10;
+// how many loops are added to a level, how many walls are tried for them, and
+// how far around a wall has to be for knocking it through to be worth it
+pub static EXTRA_LOOPS: u32 = 3;
+pub static LOOP_TRIES: u32 = 50;
+pub static LOOP_MIN_DETOUR: usize = 20;
+// how many doors start out open, and how likely a level is to have a locked one
+pub static OPEN_DOOR_PERCENT: u32 = 25;
+pub static LOCKED_DOOR_PERCENT: u32 = 40;
 
 pub static FOV_ALGO: FovAlgorithm = FovAlgorithm::Basic;
 pub static FOV_LIGHT_WALLS: bool = true;
 pub static TORCH_RADIUS: i32 = 10;
+// how bright a tile must be lit to be seen, and how strongly light tints it
+pub static MIN_VISIBLE_LIGHT: f32 = 0.1;
+pub static LIGHT_TINT: f32 = 0.4;
+pub static WALL_TORCH_RADIUS: i32 = 6;
+pub static FUNGUS_RADIUS: i32 = 3;
+// how long what is left of a fireball keeps burning
+pub static FIRE_BURN_TURNS: i32 = 10;
+
+// how many tiles a monster may search for a path each turn, and how much a
+// tile with someone standing on it counts for
+pub static PATH_MAX_NODES: usize = 400;
+pub static PATH_OCCUPIED_COST: i32 = 8;
+
+// chance out of 100 an attack hits before accuracy and evasion, and how
+// far they can push it either way
+pub static BASE_HIT_PERCENT: i32 = 80;
+pub static MIN_HIT_PERCENT: i32 = 5;
+pub static MAX_HIT_PERCENT: i32 = 95;
+pub static CRIT_PERCENT: i32 = 5;
+// the least any hit does, however good the armor
+pub static MIN_DAMAGE: i32 = 1;
+
+pub static MONSTER_SIGHT_RADIUS: i32 = 8;
+// an idle monster sets off wandering one turn in this many, at most this far
+pub static WANDER_CHANCE: u32 = 10;
+pub static WANDER_DISTANCE: i32 = 8;
+// archers back off when the player gets closer than this many tiles
+pub static ARCHER_DISTANCE: i32 = 3;
+// chance out of 100 that a fired arrow, bolt or stone breaks on landing
+pub static AMMO_BREAK_PERCENT: u32 = 25;
+// thrown things fly this far, less a tile for every two of their weight,
+// but never less than the minimum
+pub static THROW_RANGE: i32 = 8;
+pub static THROW_MIN_RANGE: i32 = 2;
+// how far around where it lands a thrown potion splashes
+pub static SHATTER_RADIUS: i32 = 2;
 
 pub static PLAYER: usize = 0;
 
+pub static STAIRS_DOWN: &str = "stairs down";
+pub static STAIRS_UP: &str = "stairs up";
+
 pub static INVENTORY_WIDTH: i32 = 50;
 
-pub static HEAL_AMOUNT: i32 = 40;
+pub static TELEPORT_TRIES: i32 = 100;
 
-pub static LIGHTNING_DAMAGE: i32 = 40;
-pub static LIGHTNING_RANGE: i32 = 5;
+pub static LAVA_DAMAGE: i32 = 10;
+pub static CHASM_FALL_DAMAGE: i32 = 5;
 
-pub static CONFUSE_RANGE: i32 = 8;
-pub static CONFUSE_NUM_TURNS: i32 = 10;
+// how likely each chance at a trap in an area is to place one
+pub static TRAP_PERCENT: u32 = 25;
+pub static DART_DAMAGE: i32 = 4;
+pub static DART_POISON_TURNS: i32 = 5;
+pub static GAS_RADIUS: f32 = 2.0;
+pub static GAS_CONFUSE_TURNS: i32 = 5;
+// how far and how well a search looks for traps, and how likely one right
+// next to the player is noticed in passing each turn
+pub static SEARCH_RADIUS: f32 = 3.0;
+pub static SEARCH_PERCENT: u32 = 60;
+pub static PERCEPTION_PERCENT: u32 = 10;
 
-pub static FIREBALL_RADIUS: i32 = 3;
-pub static FIREBALL_DAMAGE: i32 = 25;
+pub static NORMAL_SPEED: i32 = 100;
+pub static ACTION_COST: i32 = 100;
 
 pub static LEVEL_UP_BASE: i32 = 200;
 pub static LEVEL_UP_FACTOR: i32 = 150;
 
 pub static LEVEL_SCREEN_WIDTH: i32 = 40;
 pub static CHARACTER_SCREEN_WIDTH: i32 = 30;
+pub static LOOK_WIDTH: i32 = 30;
+// how long a projectile is shown on each tile of its flight
+pub static PROJECTILE_FRAME_MS: u64 = 25;
+
+pub static REPLAY_FRAMES_PER_COMMAND: i32 = 8;
+pub static REPLAY_FAST_COMMANDS_PER_FRAME: i32 = 4;
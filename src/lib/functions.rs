@@ -1,7 +1,4 @@
-use tcod::input::{Mouse, Key};
-use tcod::map::Map as FovMap;
-use tcod::colors::*;
-use tcod::input::{self, Event};
+use tcod::line::Line;
 use rand::Rng;
 use rand::distributions::{Weighted, WeightedChoice, IndependentSample};
 
@@ -9,123 +6,7 @@ use super::*;
 
 pub use super::constants::*;
 
-pub fn main_menu(tcod: &mut Tcod) {
-    let img = tcod::image::Image::from_file("menu_background.png")
-        .ok()
-        .expect("Background image not found");
-
-    while !tcod.root.window_closed() {
-        // Show bg image, at twice the regular console resolution
-        tcod::image::blit_2x(&img, (0, 0), (-1, -1), &mut tcod.root, (0, 0));
-
-        tcod.root.set_default_foreground(colors::LIGHT_YELLOW);
-        tcod.root.print_ex(
-            SCREEN_WIDTH / 2,
-            SCREEN_HEIGHT / 2 - 4,
-            BackgroundFlag::None,
-            TextAlignment::Center,
-            "TOMBS OF THE ANCIENT KINGS",
-        );
-        tcod.root.print_ex(
-            SCREEN_WIDTH / 2,
-            SCREEN_HEIGHT - 2,
-            BackgroundFlag::None,
-            TextAlignment::Center,
-            "By Yours Truly",
-        );
-
-        let choices = &["Play a new game", "Continue last game", "Quit"];
-        let choice = menu("", choices, 24, &mut tcod.root);
-
-        match choice {
-            Some(0) => {
-                let (mut objects, mut game) = new_game(tcod);
-                play_game(&mut objects, &mut game, tcod);
-            }
-            Some(1) => {
-                match load_game() {
-                    Ok((mut objects, mut game)) => {
-                        initialize_fov(&game.map, tcod);
-                        play_game(&mut objects, &mut game, tcod);
-                    }
-                    Err(_e) => {
-                        msgbox("\nNo saved game to load.\n", 24, &mut tcod.root);
-                        continue;
-                    }
-                }
-            }
-            Some(2) => {
-                break;
-            }
-            _ => {}
-        }
-    }
-}
-
-pub fn new_game(tcod: &mut Tcod) -> (Vec<Object>, Game) {
-
-    let mut player = Object::new(0, 0, '@', colors::WHITE, "player", true);
-    player.alive = true;
-    player.fighter = Some(Fighter {
-        base_max_hp:     100,
-        hp:         100,
-        base_defense:    1,
-        base_power:      3,
-        on_death:   DeathCallback::Player,
-        xp:         0,
-    });
-
-    let mut objects = vec![player];
-    let level = 1;
-
-    let mut game = Game {
-        map: make_map(&mut objects, level),
-        log: vec![],
-        inventory: vec![],
-        dungeon_level: 1,
-    };
-
-    let mut dagger = Object::new(0, 0, '-', colors::SKY, "dagger", false);
-    dagger.item = Some(Item::Sword);
-    dagger.equipment = Some(Equipment {
-        equipped: true,
-        slot: Slot::LeftHand,
-        max_hp_bonus: 0,
-        defense_bonus: 0,
-        power_bonus: 2,
-    });
-    game.inventory.push(dagger);
-
-    initialize_fov(&game.map, tcod);
-
-    game.log.add(
-        "Welcome stranger! Prepare to perish.",
-        colors::RED,
-    );
-
-    (objects, game)
-}
-
-/// Advance to the next level
-fn next_level(tcod: &mut Tcod, objects: &mut Vec<Object>, game: &mut Game) {
-    game.log.add(
-        "You take a moment to rest, and recover your strength.",
-        colors::VIOLET,
-    );
-    let heal_hp = objects[PLAYER].max_hp(game) / 2;
-    objects[PLAYER].heal(heal_hp, game);
-
-    game.log.add(
-        "After a rare moment of peace, you descend deeper into \
-        the heart of the dungeon...",
-        colors::RED,
-    );
-    game.dungeon_level += 1;
-    game.map = make_map(objects, game.dungeon_level);
-    initialize_fov(&game.map, tcod);
-}
-
-fn from_dungeon_level(table: &[Transition], level: u32) -> u32 {
+pub fn from_dungeon_level(table: &[Transition], level: u32) -> u32 {
     table
         .iter()
         .rev()
@@ -133,430 +14,69 @@ fn from_dungeon_level(table: &[Transition], level: u32) -> u32 {
         .map_or(0, |transition| transition.value)
 }
 
-fn initialize_fov(map: &Map, tcod: &mut Tcod) {
-    for y in 0..MAP_HEIGHT {
-        for x in 0..MAP_WIDTH {
-            tcod.fov.set(
-                x,
-                y,
-                !map[x as usize][y as usize].block_sight,
-                !map[x as usize][y as usize].blocked,
-            );
-        }
-    }
-    tcod.con.clear();
-}
-
-pub fn play_game(objects: &mut Vec<Object>, game: &mut Game, tcod: &mut Tcod) {
-
-    let mut previous_player_position = (-1, -1);
-
-    let mut key = Default::default();
-
-    while !tcod.root.window_closed() {
-        tcod.con.clear();
-
-        match input::check_for_event(input::MOUSE | input::KEY_PRESS) {
-            Some((_, Event::Mouse(m))) => tcod.mouse = m,
-            Some((_, Event::Key(k))) => key = k,
-            _ => key = Default::default(),
-        }
-        
-        let fov_recompute = previous_player_position != (objects[PLAYER].x, objects[PLAYER].y);
-
-        render_all(
-            tcod,
-            &objects, 
-            game,
-            fov_recompute,
-            );
-
-        tcod.root.flush();
-
-        level_up(objects, game, tcod);
-
-        let player = &mut objects[PLAYER];
-        previous_player_position = (player.x, player.y);
-        let player_action = handle_keys(key, tcod, game, objects);
-
-        if player_action == PlayerAction::Exit {
-            save_game(objects, game).unwrap();
-            break;
-        }
-
-        if objects[PLAYER].alive && player_action != PlayerAction::DidntTakeTurn {
-            for id in 0..objects.len() {
-                if objects[id].ai.is_some() {
-                    ai_take_turn(id, game, objects, &tcod.fov);
-                }
-            }
-        }
-    }
-}
-
-fn save_game(objects: &[Object], game: &Game) -> Result<(), Box<Error>> {
-    let save_data = serde_json::to_string(&(objects, game))?;
-    let mut file = File::create("savegame")?;
-    file.write_all(save_data.as_bytes())?;
-    Ok(())
-}
-
-fn load_game() -> Result<(Vec<Object>, Game), Box<Error>> {
-    let mut json_save_state = String::new();
-    let mut file = File::open("savegame")?;
-    file.read_to_string(&mut json_save_state)?;
-    let result = serde_json::from_str::<(Vec<Object>, Game)>(&json_save_state)?;
-    Ok(result)
-}
-
-pub fn handle_keys(
-    key: Key,
-    tcod: &mut Tcod,
-    game: &mut Game,
+pub fn make_map(
     objects: &mut Vec<Object>,
-    ) -> PlayerAction {
-    use PlayerAction::*;
-    use tcod::input::KeyCode::*;
-
-    let player_alive = objects[PLAYER].alive;
-    match (key, player_alive) {
-        (
-        Key {
-            code: Enter,
-            alt: true,
-            ..
-        },
-        _,
-        ) => {
-            // alt+enter to toggle fullscreen
-            let fullscreen = tcod.root.is_fullscreen();
-            tcod.root.set_fullscreen(!fullscreen);
-            DidntTakeTurn
-        }
-        (Key { code: Escape, .. }, _) => Exit, //exit game
-
-        (Key { code: Up, .. }, true) | (Key { code: NumPad8, .. }, true) => {
-            player_move_or_attack(0, -1, objects, game);
-            TookTurn
-        }
-
-        (Key { code: Down, .. }, true) | (Key { code: NumPad2, .. }, true) => {
-            player_move_or_attack(0, 1, objects, game);
-            TookTurn
-        }
-
-        (Key { code: Left, .. }, true) | (Key { code: NumPad4, .. }, true) => {
-            player_move_or_attack(-1, 0, objects, game);
-            TookTurn
-        }
-
-        (Key { code: Right, .. }, true) | (Key { code: NumPad6, .. }, true) => {
-            player_move_or_attack(1, 0, objects, game);
-            TookTurn
-        }
-
-        (Key { code: Home, .. }, true) | (Key { code: NumPad7, .. }, true) => {
-            player_move_or_attack(-1, -1, objects, game);
-            TookTurn
-        }
-
-        (Key { code: PageUp, .. }, true) | (Key { code: NumPad9, .. }, true) => {
-            player_move_or_attack(1, -1, objects, game);
-            TookTurn
-        }
-
-        (Key { code: End, .. }, true) | (Key { code: NumPad1, .. }, true) => {
-            player_move_or_attack(-1, 1, objects, game);
-            TookTurn
-        }
-
-        (Key { code: PageDown, .. }, true) | (Key { code: NumPad3, .. }, true) => {
-            player_move_or_attack(1, 1, objects, game);
-            TookTurn
-        }
-
-        (Key { code: NumPad5, .. }, true)  => {
-            TookTurn
-        }
-        (Key { printable: 'g', .. }, true) => {
-            //pick up item
-            let item_id = objects
-                .iter()
-                .position(|object| object.pos() == objects[PLAYER].pos() && object.item.is_some());
-            if let Some(item_id) = item_id {
-                pick_item_up(item_id, objects, game);
-            }
-            DidntTakeTurn
-        }
-
-        (Key { printable: 'i', .. }, true) => {
-            // show the inventory
-            let inventory_index = inventory_menu(
-                &game.inventory,
-                "Press the key next to an item to use it, or any other to cancel. \n",
-                &mut tcod.root,
-            );
-
-            if let Some(inventory_index) = inventory_index {
-                use_item(inventory_index, objects, game, tcod);
-            }
-            DidntTakeTurn
-        }
-        (Key { printable: 'd', .. }, true) => {
-            // show the inventory
-            let inventory_index = inventory_menu(
-                &game.inventory,
-                "Press the key next to an item to drop it, or any other to cancel. \n",
-                &mut tcod.root,
-            );
-
-            if let Some(inventory_index) = inventory_index {
-                drop_item(inventory_index, game, objects);
-            }
-            DidntTakeTurn
-        }
-
-        (Key { printable: '.', .. }, true) => {
-            // go down stairs, if the player is on them
-            let player_on_stairs = objects
-                .iter()
-                .any(|object| object.pos() == objects[PLAYER].pos() &&
-                     object.name == "stairs");
-            if player_on_stairs {
-                next_level(tcod, objects, game);
-            }
-            DidntTakeTurn
-        }
-
-        (Key { printable: 'c', .. }, true) => {
-            let player = &objects[PLAYER];
-            let level = player.level;
-            let level_up_xp = LEVEL_UP_BASE + player.level * LEVEL_UP_FACTOR;
-            if let Some(fighter) = player.fighter.as_ref() {
-                let msg = format!(
-                    "Character information
-
-                    Level: {}
-                    Experience: {}
-                    Experience to next level: {}
-
-                    Max HP: {}
-                    Atk: {}
-                    Def: {}",
-                    level, fighter.xp, level_up_xp, player.max_hp(game), player.power(game), player.defense(game)
-                );
-                msgbox(&msg, CHARACTER_SCREEN_WIDTH, &mut tcod.root);
-            }
-            DidntTakeTurn
-        }
-        
-        _ => DidntTakeTurn
-    }
-    
-}
-
-pub fn get_names_under_mouse(mouse: Mouse, objects: &[Object], fov_map: &FovMap) -> String {
-    let (x, y) = (mouse.cx as i32, mouse.cy as i32);
-
-    // create a list with the names of all objects at the mouse's coordinates and in FOV
-    let names = objects
-        .iter()
-        .filter(|obj| {obj.pos() == (x, y) && fov_map.is_in_fov(obj.x, obj.y)})
-        .map(|obj| obj.name.clone())
-        .collect::<Vec<_>>();
-
-    names.join(", ") // Join the names, separated by commas
-}
-
-pub fn make_map(objects: &mut Vec<Object>, level: u32) -> Map {
-    // fill map with "unblocked" tiles
-    let mut map = vec![vec![Tile::wall(); MAP_HEIGHT as usize]; MAP_WIDTH as usize];
-
+    level: u32,
+    rng: &mut GameRng,
+    catalog: &Catalog,
+) -> Map {
     assert_eq!(&objects[PLAYER] as *const _, &objects[0] as *const _);
     objects.truncate(1);
 
-    let mut rooms = vec![];
+    let generator = generator_for_level(level, rng);
+    let (mut layout, key_spot) = lay_out_level(&*generator, level, rng);
 
-    for _ in 0..MAX_ROOMS {
-        // random width and height
-        let w = rand::thread_rng().gen_range(ROOM_MIN_SIZE, ROOM_MAX_SIZE + 1);
-        let h = rand::thread_rng().gen_range(ROOM_MIN_SIZE, ROOM_MAX_SIZE + 1);
-        // random position without going out of the boundaries of the map
-        let x = rand::thread_rng().gen_range(0, MAP_WIDTH - w);
-        let y = rand::thread_rng().gen_range(0, MAP_HEIGHT - h);
-
-        let new_room = Rect::new(x, y, w, h);
-
-        let failed = rooms
-            .iter()
-            .any(|other_room| new_room.intersects_with(other_room));
-
-        if !failed {
-            // valid room because no intersections
-
-            //paint it to maps tiles
-            create_room(new_room, &mut map);
-
-            place_objects(new_room, objects, &map, level);
-
-            let (new_x, new_y) = new_room.center();
+    // nothing starts out standing in lava or over a chasm
+    let map = &layout.map;
+    for area in layout.areas.iter_mut() {
+        area.retain(|&(x, y)| !map[x as usize][y as usize].terrain.dangerous());
+    }
+    layout.areas.retain(|area| !area.is_empty());
 
-            if rooms.is_empty() {
-                // this is the first room, where the player starts
-                objects[PLAYER].set_pos(new_x, new_y);
-            } else {
-                //all rooms after the first
-                //connect to previous with tunnels
+    for area in &layout.areas {
+        place_objects(area, objects, &layout.map, level, rng, catalog);
+        place_traps(area, objects, level, rng);
+        place_lights(area, objects, &layout.map, level, rng);
+    }
+    // keep the way in and out clear of traps
+    objects.retain(|object| {
+        object.trap.is_none() || (object.pos() != layout.start && object.pos() != layout.stairs)
+    });
 
-                //center coordinates of previous room
-                let (prev_x, prev_y) = rooms[rooms.len() - 1].center();
+    let (start_x, start_y) = layout.start;
+    objects[PLAYER].set_pos(start_x, start_y);
 
-                // draw a coin (random bool value -- either true or false)
-                if rand::random() {
-                    // first horizontally, then vertically
-                    create_h_tunnel(prev_x, new_x, prev_y, &mut map);
-                    create_v_tunnel(prev_y, new_y, new_x, &mut map);
-                } else {
-                    // first vertically, then horizontally
-                    create_v_tunnel(prev_y, new_y, prev_x, &mut map);
-                    create_h_tunnel(prev_x, new_x, new_y, &mut map);
-                    
-                }
-            }
-            rooms.push(new_room);
-        }
-    }
-    let (last_room_x, last_room_y) = rooms[rooms.len() - 1].center();
-    let mut stairs = Object::new(
-        last_room_x,
-        last_room_y,
-        '<',
-        colors::WHITE,
-        "stairs",
-        false,
-    );
+    let (stairs_x, stairs_y) = layout.stairs;
+    let mut stairs = Object::new(stairs_x, stairs_y, '>', colors::WHITE, STAIRS_DOWN, false);
     stairs.always_visible = true;
     objects.push(stairs);
-    (map)
-}
 
-pub fn render_all(
-    tcod:               &mut Tcod,
-    objects:            &[Object], 
-    game:               &mut Game,
-    fov_recompute:      bool,
-  ) {
-    if fov_recompute {
-        let player = &objects[PLAYER];
-        tcod.fov
-            .compute_fov(player.x, player.y, TORCH_RADIUS, FOV_LIGHT_WALLS, FOV_ALGO);
+    if let Some((key_x, key_y)) = key_spot {
+        // the catalog always has a key
+        let key = catalog.items.iter().find(|item| item.effect == Item::Key).unwrap();
+        let mut key = key.spawn(key_x, key_y);
+        key.always_visible = true;
+        objects.push(key);
     }
 
-    for y in 0..MAP_HEIGHT {
-        for x in 0..MAP_WIDTH {
-            let visible = tcod.fov.is_in_fov(x, y);
-            let wall = game.map[x as usize][y as usize].block_sight;
-            let color = match (visible, wall) {
-                //outisde fov
-                (false, true) => COLOR_DARK_WALL,
-                (false, false) => COLOR_DARK_GROUND,
-                //inside fov
-                (true, true) => COLOR_LIGHT_WALL,
-                (true, false) => COLOR_LIGHT_GROUND,
-            };
-            let explored = &mut game.map[x as usize][y as usize].explored;
-            if visible {
-                // since it's visible, explore it
-                *explored = true;
-            }
-            if *explored {
-            tcod.con.set_char_background(x, y, color, BackgroundFlag::Set);
-            }
-        }
-    }
-
-    let mut to_draw: Vec<_> = objects
-        .iter()
-        .filter(|o| {
-            tcod.fov.is_in_fov(o.x, o.y)
-        || (o.always_visible && game.map[o.x as usize][o.y as usize].explored)
-        })
-        .collect();
-    //sort so that non-blocking objects come first
-    to_draw.sort_by(|o1, o2| { o1.blocks.cmp(&o2.blocks) });
-    // draw the objects in the list
-    for object in &to_draw {
-            object.draw(&mut tcod.con);
-        }
-
-    blit(
-        &mut tcod.con, 
-        (0, 0),
-        (MAP_WIDTH, MAP_HEIGHT), 
-        &mut tcod.root, 
-        (0, 0), 
-        1.0,
-        1.0,
-    );
-    // prepare to render GUI panel
-    tcod.panel.set_default_background(colors::BLACK);
-    tcod.panel.clear();
-
-    // print the game messages, one line at a time
-    let mut y = MSG_HEIGHT as i32;
-    for &(ref msg, color) in game.log.iter().rev() {
-        let msg_height = tcod.panel.get_height_rect(MSG_X, y, MSG_WIDTH, 0, msg);
-        y -= msg_height;
-        if y < 0 {
-            break;
-        }
-        tcod.panel.set_default_foreground(color);
-        tcod.panel.print_rect(MSG_X, y, MSG_WIDTH, 0, msg);
+    // the way back up is where the player arrives
+    if level > 1 {
+        let mut stairs = Object::new(start_x, start_y, '<', colors::WHITE, STAIRS_UP, false);
+        stairs.always_visible = true;
+        objects.push(stairs);
     }
-
-    // show the player's status
-    let hp = objects[PLAYER].fighter.map_or(0, |f| f.hp);
-    let max_hp = objects[PLAYER].max_hp(game);
-    render_bar(&mut tcod.panel, 
-                1, 
-                1, 
-                BAR_WIDTH, 
-                "HP", 
-                hp, 
-                max_hp, 
-                colors::LIGHT_RED, 
-                colors::DARKER_RED,
-    );
-
-    tcod.panel.print_ex(
-        1,
-        3,
-        BackgroundFlag::None,
-        TextAlignment::Left,
-        format!("Dungeon level: {}", game.dungeon_level),
-    );
-
-    tcod.panel.set_default_foreground(colors::LIGHT_GREY);
-    tcod.panel.print_ex(
-        1,
-        0,
-        BackgroundFlag::None,
-        TextAlignment::Left,
-        get_names_under_mouse(tcod.mouse, objects, &tcod.fov),
-    );
-
-    blit(
-        &tcod.panel, 
-        (0, 0), 
-        (SCREEN_WIDTH, SCREEN_HEIGHT), 
-        &mut tcod.root, 
-        (0, PANEL_Y), 
-        1.0, 
-        1.0
-    );
+    layout.map
 }
 
-fn place_objects(room: Rect, objects: &mut Vec<Object>, map: &Map, level: u32) {
+/// Scatter monsters and items over an area of floor
+fn place_objects(
+    area: &[(i32, i32)],
+    objects: &mut Vec<Object>,
+    map: &Map,
+    level: u32,
+    rng: &mut GameRng,
+    catalog: &Catalog,
+) {
     // choose random number of monsters
     let max_monsters = from_dungeon_level(
         &[
@@ -567,65 +87,29 @@ fn place_objects(room: Rect, objects: &mut Vec<Object>, map: &Map, level: u32) {
         level,
     );
 
-    let num_monsters = rand::thread_rng().gen_range(0, max_monsters + 1);
-    let troll_chance = from_dungeon_level(
-        &[
-            Transition { level: 3, value: 15, },
-            Transition { level: 5, value: 30, },
-            Transition { level: 7, value: 60, },
-        ],
-        level,
-    );
+    let num_monsters = rng.gen_range(0, max_monsters + 1);
 
-    let monster_chances = &mut [
-        Weighted {
-            weight: 80,
-            item: "orc",
-        },
-        Weighted {
-            weight: troll_chance,
-            item: "troll",
-        },
-    ];
+    // only monsters that can appear this deep take part in the draw
+    let monster_chances = &mut catalog
+        .monsters
+        .iter()
+        .map(|monster| Weighted {
+            weight: from_dungeon_level(&monster.spawn, level),
+            item: monster,
+        })
+        .filter(|chance| chance.weight > 0)
+        .collect::<Vec<_>>();
 
-    let monster_choice = WeightedChoice::new(monster_chances);
+    if !monster_chances.is_empty() {
+        let monster_choice = WeightedChoice::new(monster_chances);
 
-    for _ in 0..num_monsters {
-        // chose random spot for this monster
-        let x = rand::thread_rng().gen_range(room.x1 + 1, room.x2);
-        let y = rand::thread_rng().gen_range(room.y1 + 1, room.y2);
+        for _ in 0..num_monsters {
+            // chose random spot for this monster
+            let (x, y) = area[rng.gen_range(0, area.len())];
 
-        let mut monster = match monster_choice.ind_sample(&mut rand::thread_rng()) {
-            "orc" => {
-                let mut orc = Object::new(x, y, 'o', colors::DESATURATED_GREEN, "Orc", true);
-                orc.fighter = Some(Fighter {
-                    base_max_hp:     20,
-                    hp:         20,
-                    base_defense:    0,
-                    base_power:      4,
-                    on_death:   DeathCallback::Monster,
-                    xp:         35,
-                });
-                orc.ai = Some(Ai::Basic);
-                orc
-            }
-            "troll" => {
-                let mut troll = Object::new(x, y, 'T', colors::DARKER_GREEN, "Troll", true);
-                troll.fighter = Some(Fighter {
-                    base_max_hp:     30,
-                    hp:         30,
-                    base_defense:    2,
-                    base_power:      8,
-                    on_death:    DeathCallback::Monster,
-                    xp:         100,
-                });
-                troll.ai = Some(Ai::Basic);
-                troll
-            }
-            _ => unreachable!(),
-        };
-        monster.alive = true;
-        objects.push(monster);
+            let monster = monster_choice.ind_sample(rng).spawn(x, y);
+            objects.push(monster);
+        }
     }
 
     let max_items = from_dungeon_level(
@@ -636,271 +120,297 @@ fn place_objects(room: Rect, objects: &mut Vec<Object>, map: &Map, level: u32) {
         level,
     );
 
-    let item_chances = &mut [
-        Weighted {
-            weight: 35,
-            item: Item::Heal,
-        },
-        Weighted {
-            weight: from_dungeon_level(
-                        &[Transition {
-                            level: 4,
-                            value: 25,
-                        }],
-                        level,
-                    ),
-            item: Item::Lightning,
-        },
-        Weighted {
-            weight: from_dungeon_level(
-                        &[Transition {
-                            level: 6,
-                            value: 25,
-                        }],
-                        level,
-                    ),
-            item: Item::Fireball,
-        },
-        Weighted {
-            weight: from_dungeon_level(
-                        &[Transition {
-                            level: 2,
-                            value: 10,
-                        }],
-                        level,
-                    ),
-            item: Item::Confuse,
-        },
-        Weighted { 
-            weight: from_dungeon_level(
-                        &[Transition {
-                            level: 4,
-                            value: 5,
-                        }],
-                        level
-                    ),
-            item: Item::Sword,
-        },
-        Weighted { 
-            weight: from_dungeon_level(
-                        &[Transition {
-                            level: 8,
-                            value: 15,
-                        }],
-                        level
-                    ),
-            item: Item::Shield,
-        },
-    ];
+    // same as monsters: only items that can appear this deep are drawn from
+    let item_chances = &mut catalog
+        .items
+        .iter()
+        .map(|item| Weighted {
+            weight: from_dungeon_level(&item.spawn, level),
+            item,
+        })
+        .filter(|chance| chance.weight > 0)
+        .collect::<Vec<_>>();
+
+    if item_chances.is_empty() {
+        return;
+    }
 
     let item_choice = WeightedChoice::new(item_chances);
 
-    let num_items = rand::thread_rng().gen_range(0, max_items + 1);
+    let num_items = rng.gen_range(0, max_items + 1);
 
     for _ in 0..num_items {
         // choose random spot for this item
-        let x = rand::thread_rng().gen_range(room.x1 + 1, room.x2);
-        let y = rand::thread_rng().gen_range(room.y1 + 1, room.y2);
+        let (x, y) = area[rng.gen_range(0, area.len())];
 
         // only place it if the tile is not blocked
         if !is_blocked(x, y, &map, objects) {
-            let mut item = match item_choice.ind_sample(&mut rand::thread_rng()) {
-                Item::Heal => {
-                    // create a healing object
-                    let mut object = Object::new(x, y, '!', colors::VIOLET, "Healing potion", false,);
-                    object.item = Some(Item::Heal);
-                    object
-                }
-                Item::Lightning => {
-                    let mut object = Object::new(
-                        x, y, '#', colors::LIGHT_YELLOW, "Scroll of lightning bolt", false,);
-                    object.item = Some(Item::Lightning);
-                    object
-                }
-                Item::Fireball => {
-                    let mut object = Object::new(
-                        x, y, '#', colors::LIGHT_YELLOW, "Scroll of fireball", false,);
-                    object.item = Some(Item::Fireball);
-                    object
-                }
-                Item::Confuse => {
-                    let mut object = Object::new(
-                        x, y, '#', colors::LIGHT_YELLOW, "Scroll of confusion", false,);
-                    object.item = Some(Item::Confuse);
-                    object
-                }
-                Item::Sword => {
-                    // Create a sword
-                    let mut object = Object::new(x, y, '/', colors::SKY, "sword",  false,);
-                    object.item = Some(Item::Sword);
-                    object.equipment = Some(Equipment{
-                        equipped: false,
-                        slot: Slot::RightHand,
-                        power_bonus: 3,
-                        defense_bonus: 0,
-                        max_hp_bonus: 0,
-                    });
-                    object
-                }
-                Item::Shield => {
-                    // Create a shield
-                    let mut object = Object::new(x, y, '[', colors::DARKER_ORANGE, "shield",  false,);
-                    object.item = Some(Item::Shield);
-                    object.equipment = Some(Equipment{
-                        equipped: false,
-                        slot: Slot::LeftHand,
-                        power_bonus: 3,
-                        defense_bonus: 1,
-                        max_hp_bonus: 0,
-                    });
-                    object
-                }
-            };
+            let mut item = item_choice.ind_sample(rng).spawn(x, y);
             item.always_visible = true;
             objects.push(item);
         }
     }
 }
 
-fn player_move_or_attack(dx: i32, dy: i32, objects: &mut [Object], game: &mut Game) {
+pub fn player_move_or_attack(dx: i32, dy: i32, objects: &mut [Object], game: &mut Game) {
+    // a confused player stumbles in a random direction instead
+    let (dx, dy) = if objects[PLAYER].has_status(StatusKind::Confusion) {
+        (game.rng.gen_range(-1, 2), game.rng.gen_range(-1, 2))
+    } else {
+        (dx, dy)
+    };
+    // stumbling on the spot just loses the turn
+    if (dx, dy) == (0, 0) {
+        return;
+    }
+
     //coordinates player is moving to or attacking
     let x = objects[PLAYER].x + dx;
     let y = objects[PLAYER].y + dy;
 
     // try to find an attackable object
-    let target_id = objects
-        .iter()
-        .position(|object| object.fighter.is_some() && object.pos() == (x, y));
+    let target_id = (0..objects.len()).find(|&id| {
+        id != PLAYER && objects[id].fighter.is_some() && objects[id].pos() == (x, y)
+    });
 
-    //attack if target found, move otherwise
-    match target_id {
-        Some(target_id) => {
-            let (player, target) = mut_two(PLAYER, target_id, objects);
-            player.attack(target, game);
+    //attack if target found, open doors by walking into them, move otherwise
+    match (target_id, game.map[x as usize][y as usize].terrain) {
+        (Some(target_id), _) => {
+            melee(PLAYER, target_id, objects, game);
+        }
+        (None, Terrain::ClosedDoor) | (None, Terrain::LockedDoor) => {
+            player_open_door(x, y, game);
         }
-        None => {
+        (None, _) => {
             move_by(PLAYER, dx, dy, &game.map, objects);
         }
     }
 }
 
-fn level_up(
-    objects: &mut [Object],
-    game: &mut Game,
-    tcod: &mut Tcod,
-) {
-    let player = &mut objects[PLAYER];
-    let level_up_xp = LEVEL_UP_BASE + player.level * LEVEL_UP_FACTOR;
-
-    if player.fighter.as_ref().map_or(0, |f| f.xp) >= level_up_xp {
-        player.level += 1;
-        game.log.add(
-            format!(
-                "Your battle skills grow stronger! You reached level {}!",
-                player.level
-            ),
-            colors::YELLOW,
-        );
-        let fighter = player.fighter.as_mut().unwrap();
-        let mut choice = None;
-        while choice.is_none() {
-            choice = menu(
-                "Level up! Choose a stat raise: \n",
-                &[
-                format!("Constitution (+20 HP, from {})", fighter.base_max_hp),
-                format!("Strength (+1 atk, from {})", fighter.base_power),
-                format!("Agility (+1 def, from {})", fighter.base_defense),
-                ],
-                LEVEL_SCREEN_WIDTH,
-                &mut tcod.root,
-            );
-        }
-        fighter.xp -= level_up_xp;
-        match choice.unwrap() {
-            0 => {
-                fighter.base_max_hp += 20;
-                fighter.hp += 20;
-            }
-            1 => {
-                fighter.base_power +=1;
+/// The player opens a door, using up a key if it is locked. Returns whether
+/// it opened.
+pub fn player_open_door(x: i32, y: i32, game: &mut Game) -> bool {
+    if game.map[x as usize][y as usize].terrain == Terrain::LockedDoor {
+        let key = game.inventory.iter().position(|item| item.item == Some(Item::Key));
+        match key {
+            Some(key) => {
+                game.inventory.remove(key);
+                game.log.add(
+                    "You unlock the door with your key.",
+                    colors::YELLOW,
+                    MessageCategory::System,
+                );
             }
-            2 => {
-                fighter.base_defense += 1;
+            None => {
+                game.log.add("The door is locked.", colors::LIGHT_GREY, MessageCategory::System);
+                return false;
             }
-            _ => unreachable!(),
         }
+    } else {
+        game.log.add("You open the door.", colors::LIGHT_GREY, MessageCategory::System);
     }
+    open_door(x, y, game);
+    true
 }
 
-pub fn ai_take_turn(
-    monster_id: usize, 
-    game: &mut Game,
-    objects: &mut [Object], 
-    fov_map: &FovMap,
-    ) {
+pub fn open_door(x: i32, y: i32, game: &mut Game) {
+    game.map[x as usize][y as usize].terrain = Terrain::OpenDoor;
+    game.map_changed = true;
+}
+
+/// Close an open door, unless something is standing in the doorway.
+/// Returns whether it closed.
+pub fn close_door(x: i32, y: i32, objects: &[Object], game: &mut Game) -> bool {
+    if game.map[x as usize][y as usize].terrain != Terrain::OpenDoor {
+        game.log.add("There is no open door there.", colors::LIGHT_GREY, MessageCategory::System);
+        return false;
+    }
+    if objects.iter().any(|object| object.pos() == (x, y)) {
+        game.log.add("Something is in the way.", colors::LIGHT_GREY, MessageCategory::System);
+        return false;
+    }
+    game.map[x as usize][y as usize].terrain = Terrain::ClosedDoor;
+    game.map_changed = true;
+    game.log.add("You close the door.", colors::LIGHT_GREY, MessageCategory::System);
+    true
+}
+
+pub fn ai_take_turn(monster_id: usize, game: &mut Game, objects: &mut [Object]) {
     use Ai::*;
+    if objects[monster_id].has_status(StatusKind::Stun) {
+        return;
+    }
+    if objects[monster_id].has_status(StatusKind::Confusion) {
+        ai_confused(monster_id, objects, game);
+        return;
+    }
     if let Some(ai) = objects[monster_id].ai.take() {
         let new_ai = match ai {
-            Basic => ai_basic(monster_id, game, objects, fov_map),
-            Confused {
-                previous_ai,
-                num_turns,
-            } => ai_confused(monster_id, objects, game, previous_ai, num_turns),
+            Basic => ai_basic(monster_id, game, objects),
+            Archer(weapon) => ai_archer(monster_id, weapon, game, objects),
         };
         objects[monster_id].ai = Some(new_ai);
     }
 }
 
-pub fn ai_basic(
-    monster_id: usize, 
-    game: &mut Game, 
-    objects: &mut [Object], 
-    fov_map: &FovMap,
-    ) -> Ai {
-    // a basic monster takes its turn. If you can see it, it can see you
+pub fn ai_basic(monster_id: usize, game: &mut Game, objects: &mut [Object]) -> Ai {
+    // a basic monster goes after the player while it can see them, then
+    // searches where they were last seen, and otherwise idles or wanders
     let (monster_x, monster_y) = objects[monster_id].pos();
-    if fov_map.is_in_fov(monster_x, monster_y) {
-        if objects[monster_id].distance_to(&objects[PLAYER]) >= 2.0 {
-            // move towards player if far away
-            let (player_x, player_y) = objects[PLAYER].pos();
-            move_towards(monster_id, player_x, player_y, &game.map, objects);
-        } else if objects[PLAYER].fighter.map_or(false, |f| f.hp > 0) {
+    let sees_player = monster_sees_player(monster_id, objects, &game.map);
+    if sees_player {
+        let (player_x, player_y) = objects[PLAYER].pos();
+        objects[monster_id].ai_state = AiState::Hunt { x: player_x, y: player_y };
+    }
+
+    match objects[monster_id].ai_state {
+        AiState::Hunt { .. }
+            if sees_player && objects[monster_id].distance_to(&objects[PLAYER]) < 2.0 =>
+        {
             // close enough to attack! (if the player is still alive)
-            let (monster, player) = mut_two(monster_id, PLAYER, objects);
-            monster.attack(player, game);
+            if objects[PLAYER].fighter.is_some_and(|f| f.hp > 0) {
+                melee(monster_id, PLAYER, objects, game);
+            }
+        }
+        AiState::Hunt { x, y } => {
+            if (monster_x, monster_y) == (x, y) {
+                // the trail went cold, look around instead
+                objects[monster_id].ai_state = random_wander(monster_x, monster_y, game);
+            } else {
+                move_along_path(monster_id, x, y, game, objects);
+            }
+        }
+        AiState::Wander { x, y } => {
+            move_along_path(monster_id, x, y, game, objects);
+            // stop once there, or when something is in the way
+            if objects[monster_id].pos() == (monster_x, monster_y) {
+                objects[monster_id].ai_state = AiState::Idle;
+            }
+        }
+        AiState::Idle => {
+            if game.rng.gen_weighted_bool(WANDER_CHANCE) {
+                objects[monster_id].ai_state = random_wander(monster_x, monster_y, game);
+            }
         }
     }
     Ai::Basic
 }
 
+/// Whether a monster can make out the player: within its sight radius, with
+/// nothing blocking the view in between. Blind monsters and invisible players
+/// only notice each other side by side.
+pub fn monster_sees_player(monster_id: usize, objects: &[Object], map: &Map) -> bool {
+    let monster = &objects[monster_id];
+    let player = &objects[PLAYER];
+    let distance = monster.distance_to(player);
+    if distance < 2.0 {
+        return true;
+    }
+    let sight = monster.fighter.map_or(0, |f| f.sight);
+    if distance > sight as f32
+        || monster.has_status(StatusKind::Blindness)
+        || player.hidden_from(monster)
+    {
+        return false;
+    }
+    Line::new(monster.pos(), player.pos())
+        .take_while(|&pos| pos != player.pos())
+        .all(|(x, y)| !map[x as usize][y as usize].block_sight())
+}
+
+/// Pick a nearby open spot to wander to, staying idle if none turns up
+fn random_wander(x: i32, y: i32, game: &mut Game) -> AiState {
+    let target_x = x + game.rng.gen_range(-WANDER_DISTANCE, WANDER_DISTANCE + 1);
+    let target_y = y + game.rng.gen_range(-WANDER_DISTANCE, WANDER_DISTANCE + 1);
+    let on_map = target_x >= 0 && target_y >= 0 && target_x < MAP_WIDTH && target_y < MAP_HEIGHT;
+    if on_map && !game.map[target_x as usize][target_y as usize].blocked() {
+        AiState::Wander { x: target_x, y: target_y }
+    } else {
+        AiState::Idle
+    }
+}
+
 fn ai_confused(
     monster_id: usize,
     objects: &mut [Object],
     game: &mut Game,
-    previous_ai: Box<Ai>,
-    num_turns: i32,
-) -> Ai {
-    if num_turns >=0 {
-        // Still confused
-        // move in a random direction, and decrese number of turns remaining
-        move_by(
-            monster_id,
-            rand::thread_rng().gen_range(-1, 2),
-            rand::thread_rng().gen_range(-1, 2),
-            &game.map,
-            objects,
-        );
-        Ai::Confused {
-            previous_ai: previous_ai,
-            num_turns: num_turns - 1,
-        }
-    } else {
-        // restore previous_ai (delete this one)
-        game.log.add(
-            format!("The {} is no longer confused!", objects[monster_id].name),
-            colors::RED,
-        );
-        *previous_ai
+) {
+    // move in a random direction
+    move_by(
+        monster_id,
+        game.rng.gen_range(-1, 2),
+        game.rng.gen_range(-1, 2),
+        &game.map,
+        objects,
+    );
+}
+
+/// One object attacks another, recording the outcome as events
+pub fn melee(attacker_id: usize, target_id: usize, objects: &mut [Object], game: &mut Game) {
+    let strike = objects[attacker_id].melee_strike(game);
+    attack(attacker_id, target_id, strike, "attacks", objects, game);
+}
+
+/// One object strikes at another, up close or from afar, recording the
+/// outcome as events
+pub fn attack(
+    attacker_id: usize,
+    target_id: usize,
+    strike: Strike,
+    verb: &str,
+    objects: &mut [Object],
+    game: &mut Game,
+) {
+    let (attacker, target) = mut_two(attacker_id, target_id, objects);
+    let was_alive = target.alive;
+    let outcome = resolve_attack(strike, target.guard(game), &mut game.rng);
+    let (text, color) = match outcome {
+        Outcome::Miss => (
+            format!("{} {} {} but misses.", attacker.name, verb, target.name),
+            colors::LIGHT_GREY,
+        ),
+        Outcome::Hit { damage, critical: true } => (
+            format!(
+                "{} {} {} with a critical hit for {} hit points!",
+                attacker.name, verb, target.name, damage
+            ),
+            colors::LIGHT_ORANGE,
+        ),
+        Outcome::Hit { damage, critical: false } => (
+            format!("{} {} {} for {} hit points.", attacker.name, verb, target.name, damage),
+            colors::WHITE,
+        ),
+    };
+    game.log.add_entry(
+        Message::new(text, color, MessageCategory::Combat)
+            .by(attacker.id)
+            .to(target.id),
+    );
+    // whoever gets attacked knows where it came from
+    if target.ai.is_some() && attacker_id == PLAYER {
+        target.ai_state = AiState::Hunt { x: attacker.x, y: attacker.y };
+    }
+    let (damage, critical) = match outcome {
+        Outcome::Miss => {
+            game.events.push(GameEvent::Missed {
+                attacker: attacker.id,
+                target: target.id,
+            });
+            return;
+        }
+        Outcome::Hit { damage, critical } => (damage, critical),
+    };
+    if let Some(xp) = target.take_damage(damage, game) {
+        attacker.fighter.as_mut().unwrap().xp += xp;
+    }
+    game.events.push(GameEvent::Attacked {
+        attacker: attacker.id,
+        target: target.id,
+        damage,
+        critical,
+    });
+    if was_alive && !target.alive {
+        game.events.push(GameEvent::Died { id: target.id });
     }
 }
 
@@ -909,18 +419,36 @@ pub fn pick_item_up(
     objects:    &mut Vec<Object>,
     game: &mut Game,
 ) {
-    if game.inventory.len() >= 26 {
+    // ammunition goes onto a stack of the same kind, if there is one
+    let stack = game
+        .inventory
+        .iter()
+        .position(|item| item.stacks_with(&objects[object_id]));
+    if let Some(stack) = stack {
+        let item = objects.swap_remove(object_id);
+        game.log.add(
+            format!("You picked up {}!", item.stack_name()),
+            colors::GREEN,
+            MessageCategory::Items,
+        );
+        game.events.push(GameEvent::PickedUp { name: item.name.clone() });
+        game.inventory[stack].count += item.count;
+    } else if game.inventory.len() >= 26 {
         game.log.add(
             format!(
                 "Your inventory is full, cannot pick up {}.",
                 objects[object_id].name
             ),
             colors::RED,
+            MessageCategory::Items,
         );
     } else {
         let item = objects.swap_remove(object_id);
-        game.log.add( format!("You picked up a {}!", item.name),
-            colors::GREEN,);
+        game.log.add( format!("You picked up {}!", item.stack_name()),
+            colors::GREEN,
+            MessageCategory::Items,
+        );
+        game.events.push(GameEvent::PickedUp { name: item.name.clone() });
         let index = game.inventory.len();
         let slot = item.equipment.map(|e| e.slot);
         game.inventory.push(item);
@@ -940,12 +468,14 @@ pub fn monster_death(monster: &mut Object, game: &mut Game) {
                 monster.fighter.unwrap().xp
             ), 
         colors::ORANGE,
+        MessageCategory::Combat,
     );
     monster.char = '%';
     monster.color = colors::DARK_RED;
     monster.blocks = false;
     monster.fighter = None;
     monster.ai = None;
+    monster.effects.clear();
     monster.name = format!("remains of {}", monster.name);
 }
 
@@ -954,6 +484,7 @@ pub fn player_death(player: &mut Object, game: &mut Game) {
     game.log.add(
         format!("You died!"),
         colors::RED,
+        MessageCategory::Combat,
         );
 
     //for added effect, transform the player into a corpse!
@@ -975,7 +506,7 @@ pub fn create_v_tunnel(y1: i32, y2: i32, x: i32, map: &mut Map) {
 
 pub fn is_blocked(x: i32, y: i32, map: &Map, objects: &[Object]) -> bool {
     //check for blocking tile
-    if map[x as usize][y as usize].blocked {
+    if map[x as usize][y as usize].blocked() {
         return true;
     }
 
@@ -1006,6 +537,26 @@ pub fn move_towards(id: usize, target_x: i32, target_y: i32, map: &Map, objects:
     move_by(id, dx, dy, map, objects);
 }
 
+/// Take one step along the shortest path to the target, going around walls
+/// and other monsters. Heads straight for it if no path turns up in time.
+pub fn move_along_path(
+    id: usize,
+    target_x: i32,
+    target_y: i32,
+    game: &mut Game,
+    objects: &mut [Object],
+) {
+    let (x, y) = objects[id].pos();
+    let path = find_path((x, y), (target_x, target_y), &game.map, objects, PATH_MAX_NODES);
+    let door = |(x, y): (i32, i32)| game.map[x as usize][y as usize].terrain == Terrain::ClosedDoor;
+    match path.as_ref().and_then(|path| path.first()) {
+        // a closed door in the way takes a turn to open
+        Some(&(next_x, next_y)) if door((next_x, next_y)) => open_door(next_x, next_y, game),
+        Some(&(next_x, next_y)) => move_by(id, next_x - x, next_y - y, &game.map, objects),
+        None => move_towards(id, target_x, target_y, &game.map, objects),
+    }
+}
+
 pub fn mut_two<T>(first_index: usize, second_index: usize, items: &mut [T]) -> (&mut T, &mut T) {
     assert_ne!(first_index, second_index);
     let split_at_index = cmp::max(first_index, second_index);
@@ -1018,304 +569,78 @@ pub fn mut_two<T>(first_index: usize, second_index: usize, items: &mut [T]) -> (
 }
 
 
-pub fn render_bar(
-    panel: &mut Offscreen,
-    x:              i32,
-    y:              i32,
-    total_width:    i32,
-    name:           &str,
-    value:          i32,
-    maximum:        i32,
-    bar_color:      Color,
-    back_color:     Color,
-    ) {
-    // render a bar (HP, experience, etc.) First calculate the width of the bar
-    let bar_width = (value as f32 / maximum as f32 * total_width as f32) as i32;
-
-    // render the background first
-    panel.set_default_background(back_color);
-    panel.rect(x, y, total_width, 1, false, BackgroundFlag::Screen);
-
-    // now render the bar on top
-    panel.set_default_background(bar_color);
-    if bar_width > 0 {
-        panel.rect(x, y, bar_width, 1, false, BackgroundFlag::Screen);
-    }
-
-    //finally some centered text with values
-    panel.set_default_foreground(colors::WHITE);
-    panel.print_ex(x + total_width / 2, 
-                   y, 
-                   BackgroundFlag::None, 
-                   TextAlignment::Center, 
-                   &format!("{}: {}/{}", name, value, maximum),
-                   );
-}
-
-fn menu<T: AsRef<str>>(header: &str, options: &[T], width: i32, root: &mut Root)
--> Option<usize> {
-    //body
-    assert!(
-        options.len() <= 26,
-        "Cannot have a menu with more thatn 26 options."
-    );
-
-    let header_height = if header.is_empty() {
-        0
-    } else {
-        root.get_height_rect(0, 0, width, constants::SCREEN_HEIGHT, header)
-    };
-
-    let height = options.len() as i32 + header_height;
-
-    let mut window = Offscreen::new(width, height);
-
-    window.set_default_foreground(colors::WHITE);
-    window.print_rect_ex(
-        0,
-        0,
-        width,
-        height,
-        BackgroundFlag::None,
-        TextAlignment::Left,
-        header,
-    );
-
-    for (index, option_text) in options.iter().enumerate() {
-        let menu_letter = (b'a' + index as u8) as char;
-        let text = format!("({}) {}", menu_letter, option_text.as_ref());
-        window.print_ex(
-            0,
-            header_height + index as i32,
-            BackgroundFlag::None,
-            TextAlignment::Left,
-            text,
-        );
-    }
-    let x = constants::SCREEN_WIDTH / 2 - width /2;
-    let y = constants::SCREEN_HEIGHT / 2 - height /2;
-    tcod::console::blit(&mut window, (0, 0), (width, height), root, (x, y), 1.0, 0.7);
-    
-    root.flush();
-    let key = root.wait_for_keypress(true);
-
-    if key.printable.is_alphabetic() {
-        let index = key.printable.to_ascii_lowercase() as usize - 'a' as usize;
-        if index < options.len() {
-            Some(index)
-        } else {
-            None
-        }
-    } else {
-        None
-    }
-}
-
-fn msgbox(text: &str, width: i32, root: &mut Root) {
-    let options: &[&str] = &[];
-    menu(text, options, width, root);
-}
-
-fn inventory_menu(inventory: &[Object], header: &str, root: &mut Root)
--> Option<usize> {
-    let options = if inventory.len() == 0 {
-        vec!["Inventory is empty.".into()]
-    } else {
-        inventory
-            .iter()
-            .map(|item| { 
-            match item.equipment {
-                Some(equipment) if equipment.equipped => {
-                    format!("{} (on {})", item.name, equipment.slot)
-                }
-                _ => item.name.clone(),
-            }
-        })
-        .collect()
-    };
-        let inventory_index = menu(header, &options, constants::INVENTORY_WIDTH, root);
-        
-        if inventory.len() > 0 {
-            inventory_index
-        } else {
-            None
-        }
-        
-}
-
 pub fn use_item(
     inventory_id: usize,
-    objects: &mut [Object],
+    target: Option<(i32, i32)>,
+    objects: &mut Vec<Object>,
     game: &mut Game,
-    tcod: &mut Tcod,
+    fov_map: &Vision,
 ) {
-    use Item::*;
-    if let Some(item) = game.inventory[inventory_id].item {
-        let on_use = match item {
-            Heal => cast_heal,
-            Lightning => cast_lightning,
-            Confuse => cast_confuse,
-            Fireball => cast_fireball,
-            Sword => toggle_equipment,
-            Shield => toggle_equipment,
+    if let Some(item) = game.inventory[inventory_id].item.clone() {
+        let name = game.inventory[inventory_id].name.clone();
+        let result = match item {
+            Item::Magic { aim, effects, .. } => {
+                cast(aim, &effects, target, objects, game, fov_map)
+            }
+            Item::Equip => toggle_equipment(inventory_id, game),
+            Item::Key => {
+                game.log.add(
+                    "Walk into a locked door to unlock it.",
+                    colors::WHITE,
+                    MessageCategory::Items,
+                );
+                UseResult::UsedAndKept
+            }
+            Item::Ammo { kind } => {
+                game.log.add(
+                    format!("Equip a {} and fire these with 'f'.", kind.launcher()),
+                    colors::WHITE,
+                    MessageCategory::Items,
+                );
+                UseResult::UsedAndKept
+            }
         };
-        match on_use(inventory_id, objects, game, tcod) {
+        match result {
             UseResult::UsedUp => {
                 // destroy after use, unless it was cancelled
 
                 game.inventory.remove(inventory_id);
+                game.events.push(GameEvent::UsedItem { name });
             }
-            UseResult::UsedAndKept => {},
+            UseResult::UsedAndKept => {
+                game.events.push(GameEvent::UsedItem { name });
+            },
             UseResult::Cancelled => {
-                game.log.add("Cancelled", colors::WHITE);
+                game.log.add("Cancelled", colors::WHITE, MessageCategory::Items);
             }
         }
     } else {
         game.log.add(
                 format!("The {} cannot be used.", game.inventory[inventory_id].name),
                 colors::WHITE,
+                MessageCategory::Items,
         );
     }
 }
 
-fn cast_heal(
-    _inventory_id: usize,
-    objects: &mut [Object],
-    game: &mut Game,
-    _tcod: &mut Tcod,
-    ) -> UseResult {
-        // heal the player
-        if let Some(fighter) = objects[PLAYER].fighter {
-            if fighter.hp == objects[PLAYER].max_hp(game) {
-                game.log.add("You are already at full health.",
-                        colors::RED);
-                return UseResult::Cancelled;
-            }
-            game.log.add(
-                "Your wounds start to feel better!",
-                colors::LIGHT_VIOLET,
-            );
-            objects[PLAYER].heal(HEAL_AMOUNT, game);
-            return UseResult::UsedUp;
-        }
-        UseResult::Cancelled
-}
-
-fn cast_lightning(
-    _inventory_id: usize,
-    objects: &mut [Object],
-    game: &mut Game,
-    tcod: &mut Tcod,
-) -> UseResult {
-    // find closest enemy (inside a maximum range and damage it)
-    let monster_id = closest_monster(LIGHTNING_RANGE, objects, tcod);
-    if let Some(monster_id) = monster_id {
-        // zap it
-        game.log.add(
-            format!(
-                "A lightning bolt strikes the {} with a loud clap! \
-                The damage is {} hit points",
-                objects[monster_id].name, LIGHTNING_DAMAGE
-            ),
-            colors::LIGHT_BLUE,
-        );
-        if let Some(xp) = objects[monster_id].take_damage(LIGHTNING_DAMAGE, game) {
-            objects[PLAYER].fighter.as_mut().unwrap().xp += xp;
-        }
-        UseResult::UsedUp
-    } else {
-        game.log.add(
-            "No enemy is close enough to strike.",
-            colors::RED,
-        );
-        UseResult::Cancelled
-    }
-}
-
-fn cast_confuse(
-    _inventory_id: usize,
-    objects: &mut [Object],
-    game: &mut Game,
-    tcod: &mut Tcod,
-) -> UseResult {
-    // ask the player for a target to confuse
-    game.log.add(
-        "Left-click an enemy to confuse it, or right-click to cancel.",
-        colors::LIGHT_CYAN,
-    );
-    let monster_id = target_monster(tcod, objects, game, Some(CONFUSE_RANGE as f32));
-    if let Some(monster_id) = monster_id {
-        let old_ai = objects[monster_id].ai.take().unwrap_or(Ai::Basic);
-        // replace the monster's AI with a "confused" one;
-        // after some turns it will restore the old ai
-        objects[monster_id].ai = Some(Ai::Confused {
-            previous_ai: Box::new(old_ai),
-            num_turns: CONFUSE_NUM_TURNS,
-        });
-        game.log.add(
-            format!(
-                "The eyes of {} look vacant, as he starts to stumble around!",
-                objects[monster_id].name
-            ),
-            colors::LIGHT_GREEN,
-        );
-        UseResult::UsedUp
-    } else {
-        //no enemy found within max range
-        game.log.add("No enemy is close enough to strike.", colors::RED);
-        UseResult::Cancelled
-    }
-}
-
-fn cast_fireball(
-    _inventory_id: usize,
-    objects: &mut [Object],
-    game: &mut Game,
-    tcod: &mut Tcod,
-) -> UseResult {
-    // ask the player for a tile to throw a fireball at
-    game.log.add(
-        "Left-click a target tile for the fireball, or right-click to cancel.",
-        colors::LIGHT_CYAN,
-    );
-    let (x, y) = match target_tile(tcod, objects, game, None) {
-        Some(tile_pos) => tile_pos,
-        None => return UseResult::Cancelled,
-    };
-    game.log.add(
-        format!(
-            "The fireball explodes, burning everything within {} tiles!",
-            FIREBALL_RADIUS
-        ),
-        colors::ORANGE,
-    );
-
-    let mut xp_to_gain = 0;
-    for (id, obj) in objects.iter_mut().enumerate() {
-        if obj.distance(x, y) <= FIREBALL_RADIUS as f32 && obj.fighter.is_some() {
-            game.log.add(
-                format!(
-                    "The {} gets burned for {} hit points.",
-                    obj.name, FIREBALL_DAMAGE
-                ),
-                colors::ORANGE,
-            );
-            if let Some(xp) = obj.take_damage(FIREBALL_DAMAGE, game) {
-                if id != PLAYER {
-                    xp_to_gain += xp;
-                }
-            } 
+/// Move an object to a random free spot on the level, returning whether one
+/// turned up
+pub fn teleport(id: usize, objects: &mut [Object], game: &mut Game) -> bool {
+    // try random spots until one is free
+    for _ in 0..TELEPORT_TRIES {
+        let x = game.rng.gen_range(0, MAP_WIDTH);
+        let y = game.rng.gen_range(0, MAP_HEIGHT);
+        if !is_blocked(x, y, &game.map, objects) && !game.map[x as usize][y as usize].terrain.dangerous() {
+            objects[id].set_pos(x, y);
+            return true;
         }
     }
-    objects[PLAYER].fighter.as_mut().unwrap().xp += xp_to_gain;
-    UseResult::UsedUp
+    false
 }
 
 fn toggle_equipment(
     inventory_id: usize,
-    _objects: &mut [Object],
     game: &mut Game,
-    _tcod: &mut Tcod,
 ) -> UseResult {
 
     let equipment = match game.inventory[inventory_id].equipment {
@@ -1348,88 +673,6 @@ Option<usize> {
     None
 }
 
-fn closest_monster(max_range: i32, objects: &mut [Object], tcod: &Tcod)
-    -> Option<usize> {
-        let mut closest_enemy = None;
-        let mut closest_dist = (max_range + 1) as f32; //start with slightly more than max_range
-
-        for (id, object) in objects.iter().enumerate() {
-            if (id != PLAYER)
-                && object.fighter.is_some()
-                && object.ai.is_some()
-                && tcod.fov.is_in_fov(object.x, object.y)
-            {
-                // calc distance between object and player
-                let dist = objects[PLAYER].distance_to(object);
-                if dist < closest_dist {
-                    // it's closest, remember
-                    closest_enemy = Some(id);
-                    closest_dist = dist;
-                }
-            }
-        }
-        closest_enemy
-}
-
-fn target_tile(
-    tcod: &mut Tcod,
-    objects: &[Object],
-    game: &mut Game,
-    max_range: Option<f32>,
-) -> Option<(i32, i32)> {
-    use tcod::input::KeyCode::Escape;
-
-    loop {
-        // render the screen. This erases the inventory and shows the names
-        // of objects under the mouse
-        tcod.root.flush();
-        let event = input::check_for_event(input::KEY_PRESS | input::MOUSE)
-            .map(|e| e.1);
-
-        let mut key = None;
-        match event {
-            Some(Event::Mouse(m)) => tcod.mouse = m,
-            Some(Event::Key(k)) => key = Some(k),
-            None => {}
-        }
-        render_all(tcod, objects, game, false);
-
-        let (x, y) = (tcod.mouse.cx as i32, tcod.mouse.cy as i32);
-
-        let in_fov = (x < MAP_WIDTH) && (y < MAP_HEIGHT) && tcod.fov.is_in_fov(x, y);
-        let in_range = max_range.map_or(true, |range| objects[PLAYER].distance(x, y) <= range);
-        if tcod.mouse.lbutton_pressed && in_fov && in_range {
-            return Some((x,y))
-        }
-
-        let escape = key.map_or(false, |k| k.code == Escape);
-        if tcod.mouse.rbutton_pressed || escape {
-            return None // cancel if the player right clicked or pressed Escape
-        }
-    }
-}
-
-fn target_monster(
-    tcod: &mut Tcod,
-    objects: &[Object],
-    game: &mut Game,
-    max_range: Option<f32>,
-) -> Option<usize> {
-    loop {
-        match target_tile(tcod, objects, game, max_range) {
-            Some((x, y)) => {
-                // return the first clicked monster, otherwise continue looping
-                for (id, obj) in objects.iter().enumerate() {
-                    if obj.pos() == (x, y) && obj.fighter.is_some() && id != PLAYER {
-                        return Some(id);
-                    }
-                }
-            }
-            None => return None,
-        }
-    }
-}
-
 pub fn drop_item(
     inventory_id: usize,
     game: &mut Game,
@@ -1441,8 +684,10 @@ pub fn drop_item(
     }
     item.set_pos(objects[PLAYER].x, objects[PLAYER].y);
     game.log.add(
-        format!("You dropped a {}.", item.name),
+        format!("You dropped {}.", item.stack_name()),
         colors::YELLOW,
+        MessageCategory::Items,
     );
+    game.events.push(GameEvent::Dropped { name: item.name.clone() });
     objects.push(item);
 }
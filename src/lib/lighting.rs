@@ -0,0 +1,275 @@
+use rand::Rng;
+use tcod::map::Map as FovMap;
+
+use super::*;
+
+/// Something that gives off light, brightest where it is and fading towards
+/// the edge of its radius
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Light {
+    pub radius: i32,
+    pub color: Color,
+    /// how the light fades with distance: 1 fades evenly, higher stays
+    /// bright longer and drops off sharply near the edge
+    pub falloff: f32,
+    /// turns left before it burns out, if it ever does
+    #[serde(default)]
+    pub turns: Option<i32>,
+}
+
+impl Light {
+    /// How strongly it lights a tile `distance` away, from 1 down to 0
+    pub fn strength(&self, distance: f32) -> f32 {
+        let strength = 1.0 - (distance / (self.radius + 1) as f32).powf(self.falloff);
+        strength.max(0.0)
+    }
+
+    /// Whether it has burnt out and no longer gives off any light
+    pub fn spent(&self) -> bool {
+        self.turns.is_some_and(|turns| turns <= 0)
+    }
+}
+
+/// What the player can see: tiles in line of sight that are also lit, or
+/// close enough to touch. Also keeps how brightly, and in what color, every
+/// tile is lit for drawing.
+pub struct Vision {
+    sight: FovMap,
+    /// scratch map for working out how far each light reaches
+    reach: FovMap,
+    visible: Vec<Vec<bool>>,
+    light: Vec<Vec<Color>>,
+    brightness: Vec<Vec<f32>>,
+}
+
+impl Vision {
+    pub fn new(width: i32, height: i32) -> Self {
+        let (w, h) = (width as usize, height as usize);
+        Vision {
+            sight: FovMap::new(width, height),
+            reach: FovMap::new(width, height),
+            visible: vec![vec![false; h]; w],
+            light: vec![vec![colors::BLACK; h]; w],
+            brightness: vec![vec![0.0; h]; w],
+        }
+    }
+
+    pub fn set(&mut self, x: i32, y: i32, transparent: bool, walkable: bool) {
+        self.sight.set(x, y, transparent, walkable);
+        self.reach.set(x, y, transparent, walkable);
+    }
+
+    /// Whether the player can see the tile
+    pub fn is_in_fov(&self, x: i32, y: i32) -> bool {
+        x >= 0
+            && y >= 0
+            && x < MAP_WIDTH
+            && y < MAP_HEIGHT
+            && self.visible[x as usize][y as usize]
+    }
+
+    /// The mix of colors of all the light falling on a tile
+    pub fn light_color(&self, x: i32, y: i32) -> Color {
+        self.light[x as usize][y as usize]
+    }
+
+    /// How brightly a tile is lit, from 0 for pitch dark to 1
+    pub fn brightness(&self, x: i32, y: i32) -> f32 {
+        self.brightness[x as usize][y as usize]
+    }
+
+    /// Shine every light on the level, then work out what the player sees
+    /// from where they stand
+    pub fn compute(&mut self, objects: &[Object], inventory: &[Object]) {
+        for column in self.light.iter_mut() {
+            for light in column.iter_mut() {
+                *light = colors::BLACK;
+            }
+        }
+        for column in self.brightness.iter_mut() {
+            for brightness in column.iter_mut() {
+                *brightness = 0.0;
+            }
+        }
+
+        let mut lights: Vec<_> = objects
+            .iter()
+            .filter_map(|object| object.light.map(|light| (object.pos(), light)))
+            .filter(|(_, light)| !light.spent())
+            .collect();
+        if let Some(light) = carried_light(inventory) {
+            lights.push((objects[PLAYER].pos(), light));
+        }
+        for ((light_x, light_y), light) in lights {
+            self.shine(light_x, light_y, light);
+        }
+
+        let player = &objects[PLAYER];
+        let radius = if player.has_status(StatusKind::Blindness) { 1 } else { 0 };
+        self.sight
+            .compute_fov(player.x, player.y, radius, FOV_LIGHT_WALLS, FOV_ALGO);
+        for x in 0..MAP_WIDTH {
+            for y in 0..MAP_HEIGHT {
+                // anything right next to the player can be felt, if not seen
+                let lit = self.brightness[x as usize][y as usize] >= MIN_VISIBLE_LIGHT
+                    || player.distance(x, y) < 1.5;
+                self.visible[x as usize][y as usize] = self.sight.is_in_fov(x, y) && lit;
+            }
+        }
+    }
+
+    /// Add one light to every tile it reaches
+    fn shine(&mut self, light_x: i32, light_y: i32, light: Light) {
+        self.reach
+            .compute_fov(light_x, light_y, light.radius, FOV_LIGHT_WALLS, FOV_ALGO);
+        let radius = light.radius;
+        for x in cmp::max(0, light_x - radius)..=cmp::min(MAP_WIDTH - 1, light_x + radius) {
+            for y in cmp::max(0, light_y - radius)..=cmp::min(MAP_HEIGHT - 1, light_y + radius) {
+                if !self.reach.is_in_fov(x, y) {
+                    continue;
+                }
+                let distance = (((x - light_x).pow(2) + (y - light_y).pow(2)) as f32).sqrt();
+                let strength = light.strength(distance);
+                if strength <= 0.0 {
+                    continue;
+                }
+                let (x, y) = (x as usize, y as usize);
+                self.light[x][y] = self.light[x][y] + light.color * strength;
+                self.brightness[x][y] = (self.brightness[x][y] + strength).min(1.0);
+            }
+        }
+    }
+
+    /// Color for a tile the player sees, from its dark and lit colors and
+    /// the light falling on it
+    pub fn shade(&self, x: i32, y: i32, dark: Color, lit: Color) -> Color {
+        let shade = colors::lerp(dark, lit, self.brightness(x, y));
+        // the light is scaled back up to full strength, so that it only
+        // tints the tile and doesn't darken it
+        let light = self.light_color(x, y);
+        let strongest = cmp::max(light.r, cmp::max(light.g, light.b));
+        if strongest == 0 {
+            return shade;
+        }
+        let tint = light * (255.0 / f32::from(strongest));
+        colors::lerp(shade, shade * tint, LIGHT_TINT)
+    }
+}
+
+/// The brightest light the player has equipped, if any
+pub fn carried_light(inventory: &[Object]) -> Option<Light> {
+    inventory
+        .iter()
+        .filter_map(|item| item.equipment)
+        .filter(|equipment| equipment.equipped)
+        .filter_map(|equipment| equipment.light)
+        .max_by_key(|light| light.radius)
+}
+
+/// Let lights that go out by themselves burn down by a turn. Those that go
+/// out stay where they are until `clear_spent_lights`.
+pub fn burn_down_lights(objects: &mut [Object]) {
+    for object in objects.iter_mut() {
+        if let Some(Light { turns: Some(ref mut turns), .. }) = object.light {
+            if *turns > 0 {
+                *turns -= 1;
+            }
+        }
+    }
+}
+
+/// Clear away whatever has burnt out. This moves everything after it along
+/// in the list, so it is only done between turns.
+pub fn clear_spent_lights(objects: &mut Vec<Object>) {
+    objects.retain(|object| object.light.is_none_or(|light| !light.spent()));
+}
+
+/// A torch burning in a wall bracket
+pub fn wall_torch(x: i32, y: i32) -> Object {
+    let mut torch = Object::new(x, y, '*', colors::YELLOW, "wall torch", false);
+    torch.always_visible = true;
+    torch.light = Some(Light {
+        radius: WALL_TORCH_RADIUS,
+        color: Color { r: 255, g: 170, b: 80 },
+        falloff: 1.5,
+        turns: None,
+    });
+    torch
+}
+
+/// A patch of fungus giving off a faint glow
+pub fn glowing_fungus(x: i32, y: i32) -> Object {
+    let mut fungus = Object::new(x, y, ',', colors::LIGHT_CYAN, "glowing fungus", false);
+    fungus.always_visible = true;
+    fungus.light = Some(Light {
+        radius: FUNGUS_RADIUS,
+        color: Color { r: 80, g: 255, b: 200 },
+        falloff: 1.0,
+        turns: None,
+    });
+    fungus
+}
+
+/// What is left burning after a fireball goes off
+pub fn burning_remains(x: i32, y: i32, radius: i32) -> Object {
+    let mut fire = Object::new(x, y, '&', colors::ORANGE, "burning remains", false);
+    fire.light = Some(Light {
+        radius: radius + 1,
+        color: colors::ORANGE,
+        falloff: 1.0,
+        turns: Some(FIRE_BURN_TURNS),
+    });
+    fire
+}
+
+/// Put torches on the walls around an area and fungus on its floor. Torches
+/// get rarer the deeper the level, and fungus more common.
+pub fn place_lights(
+    area: &[(i32, i32)],
+    objects: &mut Vec<Object>,
+    map: &Map,
+    level: u32,
+    rng: &mut GameRng,
+) {
+    let torch_chance = from_dungeon_level(
+        &[
+            Transition { level: 1, value: 60 },
+            Transition { level: 4, value: 40 },
+            Transition { level: 7, value: 20 },
+        ],
+        level,
+    );
+    let fungus_chance = from_dungeon_level(
+        &[
+            Transition { level: 1, value: 10 },
+            Transition { level: 4, value: 25 },
+            Transition { level: 7, value: 40 },
+        ],
+        level,
+    );
+    let free = |objects: &[Object], (x, y): (i32, i32)| {
+        !objects.iter().any(|object| object.pos() == (x, y))
+    };
+
+    if rng.gen_range(0, 100) < torch_chance {
+        // walls straight next to the floor, so the light falls in
+        let walls: Vec<_> = area
+            .iter()
+            .flat_map(|&(x, y)| vec![(x - 1, y), (x + 1, y), (x, y - 1), (x, y + 1)])
+            .filter(|&(x, y)| map[x as usize][y as usize].terrain == Terrain::Wall)
+            .collect();
+        if !walls.is_empty() {
+            let (x, y) = walls[rng.gen_range(0, walls.len())];
+            if free(objects, (x, y)) {
+                objects.push(wall_torch(x, y));
+            }
+        }
+    }
+
+    if rng.gen_range(0, 100) < fungus_chance {
+        let (x, y) = area[rng.gen_range(0, area.len())];
+        if free(objects, (x, y)) {
+            objects.push(glowing_fungus(x, y));
+        }
+    }
+}
@@ -0,0 +1,238 @@
+use tcod::colors;
+
+use super::*;
+
+/// Who an item's magic reaches
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Aim {
+    /// whoever uses it
+    Myself,
+    /// the first creature in the way of a bolt shot at an enemy within
+    /// `range` tiles
+    Bolt { range: i32 },
+    /// everyone within `radius` tiles of where a ball thrown at a visible
+    /// tile comes down
+    Ball { radius: i32 },
+}
+
+/// One thing magic does to each creature it reaches. Items list as many as
+/// they like in `data/items.json`.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Effect {
+    Damage { amount: i32 },
+    Heal { amount: i32 },
+    Status { status: StatusKind, turns: i32, potency: i32 },
+    /// moves them to a random free spot on the level
+    Teleport,
+}
+
+impl Effect {
+    /// What it does, with `who` for the one it is done to, e.g. "it"
+    pub fn description(self, who: &str) -> String {
+        match self {
+            Effect::Damage { amount } => format!("does {} damage to {}", amount, who),
+            Effect::Heal { amount } => format!("heals {} for {} hit points", who, amount),
+            Effect::Status { status, turns, .. } => {
+                format!("makes {} {} for {} turns", who, status.adjective(), turns)
+            }
+            Effect::Teleport => format!("takes {} somewhere else on the level", who),
+        }
+    }
+
+    /// Whether the numbers it comes with make sense
+    pub fn is_valid(self) -> bool {
+        match self {
+            Effect::Damage { amount } | Effect::Heal { amount } => amount > 0,
+            Effect::Status { turns, potency, .. } => turns > 0 && potency >= 0,
+            Effect::Teleport => true,
+        }
+    }
+}
+
+/// What magic with these effects reaching `aim` does, as told to the player
+pub fn magic_description(aim: Aim, effects: &[Effect]) -> String {
+    let describe = |who: &str| {
+        let parts: Vec<_> = effects.iter().map(|effect| effect.description(who)).collect();
+        parts.join(" and ")
+    };
+    match aim {
+        Aim::Myself => describe("you"),
+        Aim::Bolt { range } => format!(
+            "strikes the first creature in its way within {} tiles, and {}",
+            range,
+            describe("it")
+        ),
+        Aim::Ball { radius } => format!(
+            "bursts where it lands, and {}",
+            describe(&format!("everyone within {} tiles", radius))
+        ),
+    }
+}
+
+/// Let the player's magic loose at whoever `aim` reaches, towards `target`
+/// if it needs one
+pub fn cast(
+    aim: Aim,
+    effects: &[Effect],
+    target: Option<(i32, i32)>,
+    objects: &mut Vec<Object>,
+    game: &mut Game,
+    fov_map: &Vision,
+) -> UseResult {
+    match aim {
+        Aim::Myself => cast_on_self(effects, objects, game),
+        Aim::Bolt { range } => cast_bolt(range, effects, target, objects, game, fov_map),
+        Aim::Ball { radius } => cast_ball(radius, effects, target, objects, game, fov_map),
+    }
+}
+
+fn cast_on_self(effects: &[Effect], objects: &mut [Object], game: &mut Game) -> UseResult {
+    let only_heals = effects.iter().all(|effect| matches!(effect, Effect::Heal { .. }));
+    let full_health = objects[PLAYER].fighter.is_some_and(|f| f.hp == objects[PLAYER].max_hp(game));
+    if only_heals && full_health {
+        game.log.add("You are already at full health.", colors::RED, MessageCategory::Items);
+        return UseResult::Cancelled;
+    }
+    let mut took_hold = false;
+    for &effect in effects {
+        took_hold |= apply_effect(effect, PLAYER, objects, game);
+    }
+    if took_hold {
+        UseResult::UsedUp
+    } else {
+        UseResult::Cancelled
+    }
+}
+
+fn cast_bolt(
+    range: i32,
+    effects: &[Effect],
+    target: Option<(i32, i32)>,
+    objects: &mut [Object],
+    game: &mut Game,
+    fov_map: &Vision,
+) -> UseResult {
+    // the tile the player picked, if it is visible and in range
+    let aim = target.filter(|&(x, y)| {
+        fov_map.is_in_fov(x, y) && objects[PLAYER].distance(x, y) <= range as f32
+    });
+    let aim = match aim {
+        Some(aim) => aim,
+        None => {
+            game.log.add(
+                "No enemy is close enough to strike.",
+                colors::RED,
+                MessageCategory::Combat,
+            );
+            return UseResult::Cancelled;
+        }
+    };
+    // the bolt takes whoever gets in its way
+    let from = objects[PLAYER].pos();
+    let flight = fire(from, aim, range, ('*', colors::LIGHT_BLUE), objects, game);
+    match flight.hit.filter(|&id| objects[id].fighter.is_some()) {
+        Some(id) => {
+            for &effect in effects {
+                apply_effect(effect, id, objects, game);
+            }
+        }
+        None => game.log.add(
+            "The bolt fizzles out without touching anyone.",
+            colors::LIGHT_BLUE,
+            MessageCategory::Combat,
+        ),
+    }
+    UseResult::UsedUp
+}
+
+fn cast_ball(
+    radius: i32,
+    effects: &[Effect],
+    target: Option<(i32, i32)>,
+    objects: &mut Vec<Object>,
+    game: &mut Game,
+    fov_map: &Vision,
+) -> UseResult {
+    // the ball can only be thrown at a tile the player can see
+    let aim = match target {
+        Some((x, y)) if fov_map.is_in_fov(x, y) => (x, y),
+        _ => return UseResult::Cancelled,
+    };
+    // it goes off early against anything in the way
+    let from = objects[PLAYER].pos();
+    let flight = fire(from, aim, line_steps(from, aim), ('*', colors::ORANGE), objects, game);
+    let (x, y) = flight.landing(from);
+    game.log.add(
+        format!("The ball bursts, reaching everything within {} tiles!", radius),
+        colors::ORANGE,
+        MessageCategory::Combat,
+    );
+    let caught: Vec<_> = (0..objects.len())
+        .filter(|&id| objects[id].fighter.is_some() && objects[id].distance(x, y) <= radius as f32)
+        .collect();
+    for id in caught {
+        for &effect in effects {
+            apply_effect(effect, id, objects, game);
+        }
+    }
+    // a ball that hurts leaves flames behind
+    if effects.iter().any(|effect| matches!(effect, Effect::Damage { .. })) {
+        objects.push(burning_remains(x, y, radius));
+    }
+    UseResult::UsedUp
+}
+
+/// Do one effect of the player's magic to a creature, telling the player
+/// about it. Returns whether it took hold.
+pub fn apply_effect(effect: Effect, id: usize, objects: &mut [Object], game: &mut Game) -> bool {
+    if !objects[id].alive {
+        return false;
+    }
+    let (is, takes) = if id == PLAYER { ("are", "take") } else { ("is", "takes") };
+    let subject = objects[id].subject();    let (text, color) = match effect {
+        Effect::Damage { amount } => {
+            // tell of the blow before the death it may cause
+            game.log.add_entry(
+                Message::new(
+                    format!("{} {} {} damage.", subject, takes, amount),
+                    colors::ORANGE,
+                    MessageCategory::Combat,
+                )
+                .by(objects[PLAYER].id)
+                .to(objects[id].id),
+            );
+            if let Some(xp) = objects[id].take_damage(amount, game) {
+                if id != PLAYER {
+                    objects[PLAYER].fighter.as_mut().unwrap().xp += xp;
+                }
+                game.events.push(GameEvent::Died { id: objects[id].id });
+            }
+            return true;
+        }
+        Effect::Heal { amount } => {
+            objects[id].heal(amount, game);
+            (format!("{} {} healed.", subject, is), colors::LIGHT_VIOLET)
+        }
+        Effect::Status { status, turns, potency } => {
+            objects[id].add_status(StatusEffect { kind: status, turns, potency });
+            (format!("{} {} {}!", subject, is, status.adjective()), colors::LIGHT_VIOLET)
+        }
+        Effect::Teleport => {
+            if !teleport(id, objects, game) {
+                game.log.add(
+                    "The magic fizzles; there is nowhere to go.",
+                    colors::RED,
+                    MessageCategory::Items,
+                );
+                return false;
+            }
+            (format!("Space folds around {}!", subject.to_lowercase()), colors::LIGHT_FUCHSIA)
+        }
+    };
+    game.log.add_entry(
+        Message::new(text, color, MessageCategory::Combat)
+            .by(objects[PLAYER].id)
+            .to(objects[id].id),
+    );
+    true
+}
@@ -0,0 +1,583 @@
+use rand::Rng;
+
+use super::*;
+
+/// A freshly generated level, before anything has been placed on it
+pub struct Layout {
+    pub map: Map,
+    /// patches of floor, like the rooms, that each get their own draw of
+    /// monsters and items
+    pub areas: Vec<Vec<(i32, i32)>>,
+    /// where the player arrives
+    pub start: (i32, i32),
+    /// where the stairs down go
+    pub stairs: (i32, i32),
+    /// gaps in room walls where corridors come in, to put doors in
+    pub doorways: Vec<(i32, i32)>,
+}
+
+/// Something that can lay out the tiles of a dungeon level
+pub trait MapGenerator {
+    fn generate(&self, rng: &mut GameRng) -> Layout;
+}
+
+/// Pick how a dungeon level is laid out. The first level is always plain
+/// rooms, deeper ones are more and more likely to be partitioned, and the
+/// deepest turn into caves.
+pub fn generator_for_level(level: u32, rng: &mut GameRng) -> Box<dyn MapGenerator> {
+    let cave_chance = from_dungeon_level(
+        &[
+            Transition { level: 5, value: 30 },
+            Transition { level: 7, value: 50 },
+        ],
+        level,
+    );
+    let bsp_chance = from_dungeon_level(
+        &[
+            Transition { level: 2, value: 30 },
+            Transition { level: 4, value: 50 },
+            Transition { level: 7, value: 30 },
+        ],
+        level,
+    );
+    let roll = rng.gen_range(0, 100);
+    if roll < cave_chance {
+        Box::new(Caves)
+    } else if roll < cave_chance + bsp_chance {
+        Box::new(BspRooms)
+    } else {
+        Box::new(RandomRooms)
+    }
+}
+
+/// Lay out a level with a generator and dress it up, with every part of it
+/// reachable from the start. Also returns where the key goes if a door got
+/// locked.
+pub fn lay_out_level(
+    generator: &dyn MapGenerator,
+    level: u32,
+    rng: &mut GameRng,
+) -> (Layout, Option<(i32, i32)>) {
+    let mut layout = generator.generate(rng);
+    decorate(&mut layout, level, rng);
+    add_loops(&mut layout.map, EXTRA_LOOPS, rng);
+    // after everything that could cut a region off. Doors come later, as
+    // they only go where there was floor and can all be opened, the locked
+    // one with a key left on the near side of it.
+    connect_regions(&mut layout);
+    let key_spot = add_doors(&mut layout, rng);
+    (layout, key_spot)
+}
+
+/// Rooms dropped at random where they fit, each tunnelled to the one before
+pub struct RandomRooms;
+
+impl MapGenerator for RandomRooms {
+    fn generate(&self, rng: &mut GameRng) -> Layout {
+        let mut map = vec![vec![Tile::wall(); MAP_HEIGHT as usize]; MAP_WIDTH as usize];
+        let mut rooms: Vec<Rect> = vec![];
+
+        for _ in 0..MAX_ROOMS {
+            // random width and height
+            let w = rng.gen_range(ROOM_MIN_SIZE, ROOM_MAX_SIZE + 1);
+            let h = rng.gen_range(ROOM_MIN_SIZE, ROOM_MAX_SIZE + 1);
+            // random position without going out of the boundaries of the map
+            let x = rng.gen_range(0, MAP_WIDTH - w);
+            let y = rng.gen_range(0, MAP_HEIGHT - h);
+
+            let new_room = Rect::new(x, y, w, h);
+
+            let failed = rooms
+                .iter()
+                .any(|other_room| new_room.intersects_with(other_room));
+
+            if !failed {
+                // valid room because no intersections
+                create_room(new_room, &mut map);
+
+                // all rooms after the first connect to the previous one
+                if let Some(prev_room) = rooms.last() {
+                    connect(prev_room.center(), new_room.center(), &mut map, rng);
+                }
+                rooms.push(new_room);
+            }
+        }
+
+        Layout {
+            start: rooms[0].center(),
+            stairs: rooms[rooms.len() - 1].center(),
+            doorways: doorways(&rooms, &map),
+            map,
+            areas: rooms.iter().map(Rect::tiles).collect(),
+        }
+    }
+}
+
+/// Binary space partitioning: the map is split in two again and again, a
+/// room goes in each of the final areas, and the two halves of every split
+/// are tunnelled together, so every room is reachable.
+pub struct BspRooms;
+
+impl MapGenerator for BspRooms {
+    fn generate(&self, rng: &mut GameRng) -> Layout {
+        let mut map = vec![vec![Tile::wall(); MAP_HEIGHT as usize]; MAP_WIDTH as usize];
+        let mut rooms = vec![];
+        let whole_map = Rect::new(0, 0, MAP_WIDTH - 1, MAP_HEIGHT - 1);
+        bsp_split(whole_map, BSP_DEPTH, &mut map, &mut rooms, rng);
+
+        Layout {
+            start: rooms[0].center(),
+            stairs: rooms[rooms.len() - 1].center(),
+            doorways: doorways(&rooms, &map),
+            map,
+            areas: rooms.iter().map(Rect::tiles).collect(),
+        }
+    }
+}
+
+/// Fill `area` with rooms, returning a spot inside one of them to tunnel to
+fn bsp_split(
+    area: Rect,
+    depth: u32,
+    map: &mut Map,
+    rooms: &mut Vec<Rect>,
+    rng: &mut GameRng,
+) -> (i32, i32) {
+    let width = area.x2 - area.x1;
+    let height = area.y2 - area.y1;
+    let can_split_x = width >= 2 * BSP_MIN_AREA;
+    let can_split_y = height >= 2 * BSP_MIN_AREA;
+
+    if depth == 0 || !(can_split_x || can_split_y) {
+        // a leaf: a room of random size somewhere inside the area
+        let w = rng.gen_range(ROOM_MIN_SIZE, cmp::min(ROOM_MAX_SIZE, width) + 1);
+        let h = rng.gen_range(ROOM_MIN_SIZE, cmp::min(ROOM_MAX_SIZE, height) + 1);
+        let x = rng.gen_range(area.x1, area.x2 - w + 1);
+        let y = rng.gen_range(area.y1, area.y2 - h + 1);
+        let room = Rect::new(x, y, w, h);
+        create_room(room, map);
+        rooms.push(room);
+        return room.center();
+    }
+
+    // split across the longer side, so areas don't end up as thin strips
+    let split_x = if can_split_x && can_split_y {
+        width > height
+    } else {
+        can_split_x
+    };
+    let (first, second) = if split_x {
+        let at = rng.gen_range(area.x1 + BSP_MIN_AREA, area.x2 - BSP_MIN_AREA + 1);
+        (
+            Rect { x2: at, ..area },
+            Rect { x1: at, ..area },
+        )
+    } else {
+        let at = rng.gen_range(area.y1 + BSP_MIN_AREA, area.y2 - BSP_MIN_AREA + 1);
+        (
+            Rect { y2: at, ..area },
+            Rect { y1: at, ..area },
+        )
+    };
+
+    let first_spot = bsp_split(first, depth - 1, map, rooms, rng);
+    let second_spot = bsp_split(second, depth - 1, map, rooms, rng);
+    connect(first_spot, second_spot, map, rng);
+    if rng.gen() {
+        first_spot
+    } else {
+        second_spot
+    }
+}
+
+/// Organic caves grown with a cellular automaton: random noise is smoothed
+/// until walls clump together, and only the largest open cave is kept.
+pub struct Caves;
+
+impl MapGenerator for Caves {
+    fn generate(&self, rng: &mut GameRng) -> Layout {
+        loop {
+            let mut map = cave_noise(rng);
+            for _ in 0..CAVE_SMOOTHING_STEPS {
+                map = smooth_cave(&map);
+            }
+
+            // keep the biggest cave, filling in every pocket cut off from it
+            let mut biggest: Vec<(i32, i32)> = vec![];
+            let mut seen = vec![vec![false; MAP_HEIGHT as usize]; MAP_WIDTH as usize];
+            for x in 0..MAP_WIDTH {
+                for y in 0..MAP_HEIGHT {
+                    if !seen[x as usize][y as usize] && !map[x as usize][y as usize].blocked() {
+                        let region = flood_fill(&map, (x, y));
+                        for &(x, y) in &region {
+                            seen[x as usize][y as usize] = true;
+                        }
+                        if region.len() > biggest.len() {
+                            biggest = region;
+                        }
+                    }
+                }
+            }
+            // too cramped to be worth playing, try again
+            if (biggest.len() as i32) < MAP_WIDTH * MAP_HEIGHT * CAVE_MIN_OPEN_PERCENT / 100 {
+                continue;
+            }
+            let mut cave = vec![vec![Tile::wall(); MAP_HEIGHT as usize]; MAP_WIDTH as usize];
+            for &(x, y) in &biggest {
+                cave[x as usize][y as usize] = Tile::empty();
+            }
+
+            // arrive anywhere, and find the stairs at the far end of the cave
+            let start = biggest[rng.gen_range(0, biggest.len())];
+            let stairs = *flood_fill(&cave, start).last().unwrap();
+
+            return Layout {
+                areas: cave_areas(&biggest),
+                map: cave,
+                start,
+                stairs,
+                doorways: vec![],
+            };
+        }
+    }
+}
+
+/// Every tile a wall or floor at random, with solid walls around the edge
+fn cave_noise(rng: &mut GameRng) -> Map {
+    let mut map = vec![vec![Tile::wall(); MAP_HEIGHT as usize]; MAP_WIDTH as usize];
+    for x in 1..MAP_WIDTH - 1 {
+        for y in 1..MAP_HEIGHT - 1 {
+            if rng.gen_range(0, 100) >= CAVE_WALL_PERCENT {
+                map[x as usize][y as usize] = Tile::empty();
+            }
+        }
+    }
+    map
+}
+
+/// One round of the automaton: a tile becomes wall when most of its
+/// neighbours are, and floor otherwise
+fn smooth_cave(map: &Map) -> Map {
+    let mut smoothed = map.clone();
+    for x in 1..MAP_WIDTH - 1 {
+        for y in 1..MAP_HEIGHT - 1 {
+            let walls = (-1..=1)
+                .flat_map(|dx| (-1..=1).map(move |dy| (x + dx, y + dy)))
+                .filter(|&(x, y)| map[x as usize][y as usize].blocked())
+                .count();
+            smoothed[x as usize][y as usize] = if walls >= 5 {
+                Tile::wall()
+            } else {
+                Tile::empty()
+            };
+        }
+    }
+    smoothed
+}
+
+/// Split a cave into patches by chopping the map into squares, so monsters
+/// and items spread out the same way they do over rooms
+fn cave_areas(cave: &[(i32, i32)]) -> Vec<Vec<(i32, i32)>> {
+    let columns = (MAP_WIDTH + CAVE_AREA_SIZE - 1) / CAVE_AREA_SIZE;
+    let rows = (MAP_HEIGHT + CAVE_AREA_SIZE - 1) / CAVE_AREA_SIZE;
+    let mut areas = vec![vec![]; (columns * rows) as usize];
+    for &(x, y) in cave {
+        areas[(x / CAVE_AREA_SIZE * rows + y / CAVE_AREA_SIZE) as usize].push((x, y));
+    }
+    // a few stray tiles of cave poking into a square don't count
+    areas.retain(|area| area.len() >= CAVE_AREA_SIZE as usize);
+    areas
+}
+
+/// Openings one tile wide in the walls around rooms
+fn doorways(rooms: &[Rect], map: &Map) -> Vec<(i32, i32)> {
+    let open = |(x, y): (i32, i32)| !map[x as usize][y as usize].blocked();
+    let mut doorways = vec![];
+    for room in rooms {
+        let top_and_bottom = ((room.x1 + 1)..room.x2)
+            .flat_map(|x| vec![((x, room.y1), (1, 0)), ((x, room.y2), (1, 0))]);
+        let sides = ((room.y1 + 1)..room.y2)
+            .flat_map(|y| vec![((room.x1, y), (0, 1)), ((room.x2, y), (0, 1))]);
+        for ((x, y), (dx, dy)) in top_and_bottom.chain(sides) {
+            let narrow = !open((x - dx, y - dy)) && !open((x + dx, y + dy));
+            if open((x, y)) && narrow && !doorways.contains(&(x, y)) {
+                doorways.push((x, y));
+            }
+        }
+    }
+    doorways
+}
+
+/// Hang doors in the doorways, most of them closed. Sometimes one is locked,
+/// with its key left somewhere that can be reached without going through it.
+pub fn add_doors(layout: &mut Layout, rng: &mut GameRng) -> Option<(i32, i32)> {
+    for &(x, y) in &layout.doorways {
+        let tile = &mut layout.map[x as usize][y as usize];
+        // a corridor may have been knocked through or flooded since
+        if tile.terrain == Terrain::Floor {
+            tile.terrain = if rng.gen_range(0, 100) < OPEN_DOOR_PERCENT {
+                Terrain::OpenDoor
+            } else {
+                Terrain::ClosedDoor
+            };
+        }
+    }
+
+    let doors: Vec<_> = layout
+        .doorways
+        .iter()
+        .cloned()
+        .filter(|&(x, y)| layout.map[x as usize][y as usize].terrain != Terrain::Floor)
+        .collect();
+    if doors.is_empty() || rng.gen_range(0, 100) >= LOCKED_DOOR_PERCENT {
+        return None;
+    }
+    let (x, y) = doors[rng.gen_range(0, doors.len())];
+    layout.map[x as usize][y as usize].terrain = Terrain::LockedDoor;
+    let reachable: Vec<_> = flood_fill(&layout.map, layout.start)
+        .into_iter()
+        .filter(|&(x, y)| {
+            (x, y) != layout.start && layout.map[x as usize][y as usize].terrain == Terrain::Floor
+        })
+        .collect();
+    if reachable.is_empty() {
+        // nowhere to leave the key, so leave the door unlocked
+        layout.map[x as usize][y as usize].terrain = Terrain::ClosedDoor;
+        return None;
+    }
+    Some(reachable[rng.gen_range(0, reachable.len())])
+}
+
+/// Scatter patches of grass, rubble and water over the floor, and on deeper
+/// levels lava and chasms
+pub fn decorate(layout: &mut Layout, level: u32, rng: &mut GameRng) {
+    use Terrain::*;
+    let patches = [
+        (Grass, from_dungeon_level(&[Transition { level: 1, value: 4 }], level)),
+        (Rubble, from_dungeon_level(&[Transition { level: 1, value: 3 }], level)),
+        (ShallowWater, from_dungeon_level(&[Transition { level: 1, value: 2 }], level)),
+        (
+            Lava,
+            from_dungeon_level(
+                &[Transition { level: 5, value: 1 }, Transition { level: 8, value: 2 }],
+                level,
+            ),
+        ),
+        (Chasm, from_dungeon_level(&[Transition { level: 4, value: 1 }], level)),
+    ];
+
+    for &(terrain, count) in &patches {
+        for _ in 0..count {
+            let x = rng.gen_range(1, MAP_WIDTH - 1);
+            let y = rng.gen_range(1, MAP_HEIGHT - 1);
+            let radius = rng.gen_range(1, 4);
+            for (tx, ty) in circle(x, y, radius) {
+                // dangerous terrain stays in the open, so there is always a
+                // way around it
+                let wall_nearby = (-1..=1)
+                    .flat_map(|dx| (-1..=1).map(move |dy| (tx + dx, ty + dy)))
+                    .any(|(nx, ny)| layout.map[nx as usize][ny as usize].blocked());
+                let keep_clear = (tx, ty) == layout.start
+                    || (tx, ty) == layout.stairs
+                    || (terrain.dangerous() && wall_nearby);
+                let tile = &mut layout.map[tx as usize][ty as usize];
+                if tile.terrain != Floor || keep_clear {
+                    continue;
+                }
+                // big pools are deep in the middle
+                let deep = terrain == ShallowWater
+                    && radius == 3
+                    && (tx - x).pow(2) + (ty - y).pow(2) <= 1;
+                tile.terrain = if deep { DeepWater } else { terrain };
+            }
+        }
+    }
+}
+
+/// The tiles within `radius` of a point, leaving out the edge of the map
+fn circle(x: i32, y: i32, radius: i32) -> Vec<(i32, i32)> {
+    let mut tiles = vec![];
+    for tx in cmp::max(1, x - radius)..=cmp::min(MAP_WIDTH - 2, x + radius) {
+        for ty in cmp::max(1, y - radius)..=cmp::min(MAP_HEIGHT - 2, y + radius) {
+            if (tx - x).pow(2) + (ty - y).pow(2) <= radius.pow(2) {
+                tiles.push((tx, ty));
+            }
+        }
+    }
+    tiles
+}
+
+/// Make sure every bit of floor, the stairs included, can be walked to from
+/// the start without crossing lava or a chasm, by digging a corridor from
+/// each cut-off region to the nearest reachable floor
+pub fn connect_regions(layout: &mut Layout) {
+    loop {
+        let mut reached = vec![vec![false; MAP_HEIGHT as usize]; MAP_WIDTH as usize];
+        for (x, y) in flood_fill(&layout.map, layout.start) {
+            reached[x as usize][y as usize] = true;
+        }
+        let cut_off = (0..MAP_WIDTH)
+            .flat_map(|x| (0..MAP_HEIGHT).map(move |y| (x, y)))
+            .find(|&(x, y)| {
+                let terrain = layout.map[x as usize][y as usize].terrain;
+                !terrain.blocks() && !terrain.dangerous() && !reached[x as usize][y as usize]
+            });
+        match cut_off {
+            Some(tile) => {
+                let region = flood_fill(&layout.map, tile);
+                dig_to_reached(&region, &reached, &mut layout.map);
+            }
+            None => return,
+        }
+    }
+}
+
+/// Dig the shortest straight-stepping corridor from a region to any reached tile
+fn dig_to_reached(region: &[(i32, i32)], reached: &[Vec<bool>], map: &mut Map) {
+    let mut came_from = vec![vec![None; MAP_HEIGHT as usize]; MAP_WIDTH as usize];
+    let mut queue: Vec<(i32, i32)> = region.to_vec();
+    for &(x, y) in region {
+        came_from[x as usize][y as usize] = Some((x, y));
+    }
+    let mut next = 0;
+    while next < queue.len() {
+        let (x, y) = queue[next];
+        next += 1;
+        if reached[x as usize][y as usize] {
+            // walk back to the region, digging as we go
+            let mut current = (x, y);
+            while let Some(previous) = came_from[current.0 as usize][current.1 as usize] {
+                map[current.0 as usize][current.1 as usize] = Tile::empty();
+                if previous == current {
+                    break;
+                }
+                current = previous;
+            }
+            return;
+        }
+        for &(dx, dy) in &[(1, 0), (-1, 0), (0, 1), (0, -1)] {
+            let (nx, ny) = (x + dx, y + dy);
+            // stay off the outer edge so the map keeps its border
+            if nx > 0
+                && ny > 0
+                && nx < MAP_WIDTH - 1
+                && ny < MAP_HEIGHT - 1
+                && came_from[nx as usize][ny as usize].is_none()
+            {
+                came_from[nx as usize][ny as usize] = Some((x, y));
+                queue.push((nx, ny));
+            }
+        }
+    }
+}
+
+/// Knock through up to `count` single walls between two bits of floor that
+/// are a long walk apart, so the level has some loops to run around
+pub fn add_loops(map: &mut Map, count: u32, rng: &mut GameRng) {
+    let open = |map: &Map, x: i32, y: i32| !map[x as usize][y as usize].blocked();
+    // walls with floor on two opposite sides and wall on the other two
+    let mut candidates = vec![];
+    for x in 1..MAP_WIDTH - 1 {
+        for y in 1..MAP_HEIGHT - 1 {
+            if open(map, x, y) {
+                continue;
+            }
+            let across = open(map, x - 1, y) && open(map, x + 1, y);
+            let down = open(map, x, y - 1) && open(map, x, y + 1);
+            if across && !open(map, x, y - 1) && !open(map, x, y + 1) {
+                candidates.push(((x, y), (x - 1, y), (x + 1, y)));
+            } else if down && !open(map, x - 1, y) && !open(map, x + 1, y) {
+                candidates.push(((x, y), (x, y - 1), (x, y + 1)));
+            }
+        }
+    }
+
+    let mut added = 0;
+    for _ in 0..LOOP_TRIES {
+        if added == count || candidates.is_empty() {
+            return;
+        }
+        let ((x, y), from, to) = candidates.swap_remove(rng.gen_range(0, candidates.len()));
+        // no path within the search budget is as good as a long one
+        let walk = find_path(from, to, map, &[], PATH_MAX_NODES).map(|path| path.len());
+        if walk.unwrap_or(usize::MAX) >= LOOP_MIN_DETOUR {
+            map[x as usize][y as usize] = Tile::empty();
+            added += 1;
+        }
+    }
+}
+
+/// Every tile reachable from `from` by walking, without stepping into lava
+/// or a chasm, nearest first
+pub fn flood_fill(map: &Map, from: (i32, i32)) -> Vec<(i32, i32)> {
+    let mut reached = vec![vec![false; MAP_HEIGHT as usize]; MAP_WIDTH as usize];
+    let mut tiles = vec![from];
+    reached[from.0 as usize][from.1 as usize] = true;
+    let mut next = 0;
+    while next < tiles.len() {
+        let (x, y) = tiles[next];
+        next += 1;
+        for dx in -1..=1 {
+            for dy in -1..=1 {
+                let (x, y) = (x + dx, y + dy);
+                if x >= 0
+                    && y >= 0
+                    && x < MAP_WIDTH
+                    && y < MAP_HEIGHT
+                    && !reached[x as usize][y as usize]
+                    && map[x as usize][y as usize].terrain.walkable()
+                    && !map[x as usize][y as usize].terrain.dangerous()
+                {
+                    reached[x as usize][y as usize] = true;
+                    tiles.push((x, y));
+                }
+            }
+        }
+    }
+    tiles
+}
+
+/// Tunnel between two points with a single bend
+fn connect(from: (i32, i32), to: (i32, i32), map: &mut Map, rng: &mut GameRng) {
+    let ((from_x, from_y), (to_x, to_y)) = (from, to);
+    // draw a coin (random bool value -- either true or false)
+    if rng.gen() {
+        // first horizontally, then vertically
+        create_h_tunnel(from_x, to_x, from_y, map);
+        create_v_tunnel(from_y, to_y, to_x, map);
+    } else {
+        // first vertically, then horizontally
+        create_v_tunnel(from_y, to_y, from_x, map);
+        create_h_tunnel(from_x, to_x, to_y, map);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn start_stairs_and_key_are_always_reachable() {
+        let generators: [&dyn MapGenerator; 3] = [&RandomRooms, &BspRooms, &Caves];
+        for generator in generators.iter() {
+            for seed in 0..40 {
+                let mut rng = GameRng::new(seed);
+                let level = 1 + seed as u32 % 10;
+                let (layout, key_spot) = lay_out_level(*generator, level, &mut rng);
+                // the key has to be reached before the locked door is opened
+                let reach = flood_fill(&layout.map, layout.start);
+                if let Some(key) = key_spot {
+                    assert!(reach.contains(&key), "seed {}: key cut off", seed);
+                }
+                let mut unlocked = layout.map.clone();
+                for tile in unlocked.iter_mut().flat_map(|column| column.iter_mut()) {
+                    if tile.terrain == Terrain::LockedDoor {
+                        tile.terrain = Terrain::OpenDoor;
+                    }
+                }
+                let reach = flood_fill(&unlocked, layout.start);
+                assert!(reach.contains(&layout.stairs), "seed {}: stairs cut off", seed);
+            }
+        }
+    }
+}
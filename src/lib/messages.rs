@@ -0,0 +1,186 @@
+use std::collections::VecDeque;
+
+use super::*;
+
+pub trait MessageLog {
+    fn add<T: Into<String>>(&mut self, message: T, color: Color, category: MessageCategory) {
+        self.add_entry(Message::new(message, color, category));
+    }
+
+    /// Add a message made up beforehand, e.g. to say who it is about
+    fn add_entry(&mut self, message: Message);
+}
+
+/// What a message is about, so the history can be filtered
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MessageCategory {
+    /// attacks, damage, deaths and anything else that hurts or hinders
+    Combat,
+    /// picking up, dropping, using and equipping things
+    Items,
+    /// everything else: the dungeon, prompts and the game itself
+    System,
+}
+
+impl MessageCategory {
+    pub fn name(self) -> &'static str {
+        match self {
+            MessageCategory::Combat => "combat",
+            MessageCategory::Items => "items",
+            MessageCategory::System => "system",
+        }
+    }
+
+    /// Color the category is marked with in the message history
+    pub fn color(self) -> Color {
+        match self {
+            MessageCategory::Combat => colors::LIGHT_RED,
+            MessageCategory::Items => colors::LIGHT_GREEN,
+            MessageCategory::System => colors::LIGHT_GREY,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Message {
+    pub text: String,
+    pub color: Color,
+    pub category: MessageCategory,
+    /// the turn it was first said on
+    pub turn: u32,
+    /// how many times in a row it was said
+    pub count: u32,
+    /// id of the object that did what the message is about, if any
+    #[serde(default)]
+    pub subject: Option<u64>,
+    /// id of the object it was done to, if any
+    #[serde(default)]
+    pub target: Option<u64>,
+}
+
+impl Message {
+    /// A message about nothing in particular; the log stamps the turn
+    pub fn new<T: Into<String>>(text: T, color: Color, category: MessageCategory) -> Self {
+        Message {
+            text: text.into(),
+            color,
+            category,
+            turn: 0,
+            count: 1,
+            subject: None,
+            target: None,
+        }
+    }
+
+    /// The same message, about something the object with this id did
+    pub fn by(mut self, subject: u64) -> Self {
+        self.subject = Some(subject);
+        self
+    }
+
+    /// The same message, about something done to the object with this id
+    pub fn to(mut self, target: u64) -> Self {
+        self.target = Some(target);
+        self
+    }
+
+    /// The text as shown, with how many times it was repeated
+    pub fn display(&self) -> String {
+        if self.count > 1 {
+            format!("{} x{}", self.text, self.count)
+        } else {
+            self.text.clone()
+        }
+    }
+}
+
+/// Everything the player has been told, oldest first. Only the latest
+/// `limit` messages are kept, and so saved.
+#[derive(Serialize, Deserialize)]
+pub struct Messages {
+    entries: VecDeque<Message>,
+    /// the turn new messages are stamped with
+    turn: u32,
+    /// how many messages are kept
+    #[serde(default = "default_limit")]
+    limit: usize,
+}
+
+fn default_limit() -> usize {
+    MESSAGE_HISTORY_LIMIT
+}
+
+impl Default for Messages {
+    fn default() -> Self {
+        Messages::with_limit(MESSAGE_HISTORY_LIMIT)
+    }
+}
+
+impl Messages {
+    pub fn new() -> Self {
+        Messages::default()
+    }
+
+    /// A log keeping only the latest `limit` messages
+    pub fn with_limit(limit: usize) -> Self {
+        Messages {
+            entries: VecDeque::new(),
+            turn: 0,
+            limit,
+        }
+    }
+
+    /// Stamp every message from now on with this turn
+    pub fn set_turn(&mut self, turn: u32) {
+        self.turn = turn;
+    }
+
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &Message> {
+        self.entries.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+impl MessageLog for Messages {
+    fn add_entry(&mut self, mut message: Message) {
+        // the same thing said again just counts up
+        if let Some(last) = self.entries.back_mut() {
+            let same = last.text == message.text
+                && last.category == message.category
+                && last.subject == message.subject
+                && last.target == message.target;
+            if same {
+                last.count += 1;
+                return;
+            }
+        }
+        message.turn = self.turn;
+        message.count = 1;
+        self.entries.push_back(message);
+        while self.entries.len() > self.limit {
+            self.entries.pop_front();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_only_the_latest_messages() {
+        let mut log = Messages::with_limit(3);
+        for n in 0..5 {
+            log.add(format!("message {}", n), colors::WHITE, MessageCategory::System);
+        }
+        let texts: Vec<_> = log.iter().map(|message| message.text.as_str()).collect();
+        assert_eq!(texts, ["message 2", "message 3", "message 4"]);
+    }
+}
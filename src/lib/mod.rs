@@ -1,35 +1,58 @@
+#[macro_use]
+extern crate serde_derive;
+
 use std::cmp;
-use std::io::{ Read, Write };
-use std::fs::File;
-use std::error::Error;
+use std::collections::BTreeMap;
 
 use tcod::console::*;
 use tcod::colors::{self, Color};
 use tcod::input::Mouse;
-use tcod::map::Map as FovMap;
 
+pub mod catalog;
+pub mod combat;
 pub mod constants;
 pub mod functions;
+pub mod lighting;
+pub mod magic;
+pub mod mapgen;
+pub mod messages;
+pub mod pathfinding;
+pub mod projectile;
+pub mod ranged;
+pub mod replay;
+pub mod rng;
+pub mod status;
+pub mod terrain;
+pub mod throwing;
+pub mod traps;
+pub mod ui;
+pub mod world;
+
+pub use crate::catalog::*;
+pub use crate::combat::*;
+pub use crate::constants::*;
+pub use crate::functions::*;
+pub use crate::lighting::*;
+pub use crate::magic::*;
+pub use crate::mapgen::*;
+pub use crate::messages::*;
+pub use crate::pathfinding::*;
+pub use crate::projectile::*;
+pub use crate::ranged::*;
+pub use crate::replay::*;
+pub use crate::rng::*;
+pub use crate::status::*;
+pub use crate::terrain::*;
+pub use crate::throwing::*;
+pub use crate::traps::*;
+pub use crate::ui::*;
+pub use crate::world::*;
 
-// use crate::lib::functions::*;
-pub use crate::lib::constants::*;
-pub use crate::lib::functions::*;
-
-pub trait MessageLog {
-    fn add<T: Into<String>>(&mut self, message: T, color: Color);
-}
-
-impl MessageLog for Vec<(String, Color)> {
-    fn add<T: Into<String>>(&mut self, message: T, color: Color) {
-        self.push((message.into(), color));
-    }
-}
 
 pub struct Tcod {
     pub root: Root,
     pub con: Offscreen,
     pub panel: Offscreen,
-    pub fov: FovMap,
     pub mouse: Mouse,
 }
 
@@ -39,8 +62,40 @@ pub struct Game {
     pub log: Messages,
     pub inventory: Vec<Object>,
     pub dungeon_level: u32,
+    /// the turn being played, counting the player's turns from 1
+    #[serde(default)]
+    pub turn: u32,
+    /// every visited level other than the current one, by dungeon level
+    pub levels: BTreeMap<u32, Level>,
+    /// the seed this run was started from
+    pub seed: u64,
+    pub rng: GameRng,
+    /// how many commands were issued since the start. The commands
+    /// themselves are only kept in the replay file.
+    #[serde(default)]
+    pub commands_issued: usize,
+    /// the object id last handed out
+    #[serde(default)]
+    pub last_id: u64,
+    /// events produced since the last `World::step`, drained by it
+    #[serde(skip)]
+    pub events: Vec<GameEvent>,
+    /// whether a door opened or closed, or the level changed, since vision
+    /// last went over the map
+    #[serde(skip)]
+    pub map_changed: bool,
 }
 
+/// A dungeon level the player has left, kept as it was for when they return
+#[derive(Serialize, Deserialize)]
+pub struct Level {
+    pub map: Map,
+    /// everything on the level, apart from the player
+    pub objects: Vec<Object>,
+}
+
+/// A value that applies from a given dungeon level onwards
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
 pub struct Transition {
     level: u32,
     value: u32,
@@ -48,6 +103,10 @@ pub struct Transition {
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Object {
+    /// stays the same for as long as the object exists, unlike its place in
+    /// the list of objects. 0 until the world it is put in hands it one.
+    #[serde(default)]
+    pub id:             u64,
     pub x:              i32,
     pub y:              i32,
     pub char:           char,
@@ -57,15 +116,30 @@ pub struct Object {
     pub alive:          bool,
     pub fighter:        Option<Fighter>,
     pub ai:             Option<Ai>,
+    pub ai_state:       AiState,
     pub item:           Option<Item>,
+    pub trap:           Option<Trap>,
+    pub light:          Option<Light>,
     pub always_visible: bool,
     pub level:          i32,
     pub equipment:      Option<Equipment>,
+    pub effects:        Vec<StatusEffect>,
+    /// how many there are in the stack, for things like arrows
+    #[serde(default = "single")]
+    pub count:          u32,
+    /// how heavy an item is, and so how much it hurts when thrown
+    #[serde(default)]
+    pub weight:         i32,
+}
+
+fn single() -> u32 {
+    1
 }
 
 impl Object {
     pub fn new(x: i32, y: i32, char: char, color: Color, name: &str, blocks: bool) -> Self {
         Object { 
+            id:             0,
             x, 
             y, 
             char, 
@@ -75,10 +149,16 @@ impl Object {
             alive:          false,
             fighter:        None,
             ai:             None,
+            ai_state:       AiState::Idle,
             item:           None,
+            trap:           None,
+            light:          None,
             always_visible: false,
             level:          1,
             equipment:      None,
+            effects:        vec![],
+            count:          1,
+            weight:         0,
         }
     }
 
@@ -127,26 +207,6 @@ impl Object {
         None
     }
 
-    pub fn attack(&mut self, target: &mut Object, game: &mut Game) {
-        // a simple formula for attack damage
-        let damage = self.power(game) - target.defense(game);
-        if damage > 0 {
-            //make the target take some damage
-            game.log.add(
-                format!("{} attacks {} for {} hit points.",self.name, target.name, damage),
-                colors::WHITE,
-            );
-            if let Some(xp) = target.take_damage(damage, game) {
-                self.fighter.as_mut().unwrap().xp += xp;
-            }
-        } else {
-            game.log.add(
-                format!("{} attacks {} but it has no effect!", self.name, target.name),
-                colors::WHITE,
-            );
-        }
-    }
-
     pub fn heal(&mut self, amount: i32, game: &Game) {
         let max_hp = self.max_hp(game);
         if let Some(ref mut fighter) = self.fighter {
@@ -158,11 +218,12 @@ impl Object {
     }
 
     /// Equip object and show a message about it
-    pub fn equip(&mut self, log: &mut Vec<(String, Color)>) {
+    pub fn equip(&mut self, log: &mut Messages) {
         if self.item.is_none() {
             log.add(
                 format!("Can't equip {:?} because it's not an item.", self),
                 colors::RED,
+                MessageCategory::Items,
             );
             return;
         };
@@ -173,21 +234,24 @@ impl Object {
                 log.add(
                     format!("Equipped {} on {}.", self.name, equipment.slot),
                     colors::LIGHT_GREEN,
+                    MessageCategory::Items,
                 );
             }
         } else {
             log.add(
                 format!("Can't equip {:?} because it's not an Equipment.", self),
                        colors::RED,
+                       MessageCategory::Items,
             );
         }
     }
     /// unequip object and show a message about it
-    pub fn unequip(&mut self, log: &mut Vec<(String, Color)>) {
+    pub fn unequip(&mut self, log: &mut Messages) {
         if self.item.is_none() {
             log.add(
                 format!("Can't unequip {:?} because it's not an item.", self),
                 colors::RED,
+                MessageCategory::Items,
             );
             return;
         };
@@ -198,12 +262,14 @@ impl Object {
                 log.add(
                     format!("Unequipped {} on {}.", self.name, equipment.slot),
                     colors::LIGHT_YELLOW,
+                    MessageCategory::Items,
                 );
             }
         } else {
             log.add(
                 format!("Can't unquip {:?} because it's not an Equipment.", self),
                        colors::RED,
+                       MessageCategory::Items,
             );
         }
     }
@@ -241,6 +307,18 @@ impl Object {
         base_max_hp + bonus
     }
 
+    /// Energy this object gains per tick of the turn scheduler
+    pub fn speed(&self) -> i32 {
+        let mut speed = self.fighter.map_or(0, |f| f.base_speed);
+        if self.has_status(StatusKind::Haste) {
+            speed *= 2;
+        }
+        if self.has_status(StatusKind::Slow) {
+            speed /= 2;
+        }
+        cmp::max(1, speed)
+    }
+
     pub fn get_all_equipped(&self, game: &Game) -> Vec<Equipment> {
         if self.name == "player" {
             game.inventory
@@ -257,26 +335,32 @@ impl Object {
 
 #[derive(Clone, Copy, Debug, Serialize, Deserialize)]
 pub struct Tile {
-    pub blocked: bool,
-    pub block_sight: bool,
+    pub terrain: Terrain,
     pub explored: bool,
 }
 
 impl Tile {
-    pub fn empty() -> Self {
-        Tile { 
-            blocked: false, 
-            block_sight: false,
+    pub fn new(terrain: Terrain) -> Self {
+        Tile {
+            terrain,
             explored: false,
         }
     }
 
+    pub fn empty() -> Self {
+        Tile::new(Terrain::Floor)
+    }
+
     pub fn wall() -> Self {
-        Tile { 
-            blocked: true, 
-            block_sight: true,
-            explored: false,
-        }
+        Tile::new(Terrain::Wall)
+    }
+
+    pub fn blocked(&self) -> bool {
+        self.terrain.blocks()
+    }
+
+    pub fn block_sight(&self) -> bool {
+        self.terrain.blocks_sight()
     }
 }
 
@@ -300,6 +384,13 @@ impl Rect {
         }
     }
 
+    /// The floor tiles `create_room` carves out
+    pub fn tiles(&self) -> Vec<(i32, i32)> {
+        ((self.x1 + 1)..self.x2)
+            .flat_map(|x| ((self.y1 + 1)..self.y2).map(move |y| (x, y)))
+            .collect()
+    }
+
     pub fn center(&self) -> (i32, i32) {
         let center_x = (self.x1 + self.x2) / 2;
         let center_y = (self.y1 + self.y2) / 2;
@@ -326,7 +417,6 @@ pub fn create_room(room: Rect, map: &mut Map) {
 pub enum PlayerAction {
     TookTurn,
     DidntTakeTurn,
-    Exit,
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
@@ -337,6 +427,21 @@ pub struct Fighter {
     pub base_power: i32,
     pub on_death:   DeathCallback,
     pub xp:         i32,
+    /// energy gained per tick; NORMAL_SPEED acts once per tick
+    pub base_speed: i32,
+    /// spent to act, ACTION_COST at a time
+    pub energy:     i32,
+    /// how many tiles away this creature can make things out
+    pub sight:      i32,
+    /// rolled for the damage of every blow, on top of power
+    #[serde(default)]
+    pub damage:     Dice,
+    /// better or worse chance to hit, in percent
+    #[serde(default)]
+    pub accuracy:   i32,
+    /// better or worse chance to be missed, in percent
+    #[serde(default)]
+    pub evasion:    i32,
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
@@ -359,22 +464,74 @@ impl DeathCallback {
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum Ai {
     Basic,
-    Confused {
-        previous_ai: Box<Ai>,
-        num_turns: i32,
-    },
+    /// shoots at the player from a distance, backing off when they get close
+    Archer(Ranged),
 }
 
-pub type Messages = Vec<(String, Color)>;
-
+/// What a monster is up to between turns
 #[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum AiState {
+    /// waiting for something to turn up
+    Idle,
+    /// strolling towards a random spot
+    Wander { x: i32, y: i32 },
+    /// after the player, at the position they were last seen
+    Hunt { x: i32, y: i32 },
+}
+
+/// What using an item does, as given in `data/items.json`. Magic items list
+/// the effects they have on whoever they reach, so new ones need only data.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum Item {
-    Heal,
-    Lightning,
-    Confuse,
-    Fireball,
-    Sword,
-    Shield,
+    /// does each of `effects` to whoever `aim` reaches
+    Magic {
+        aim: Aim,
+        effects: Vec<Effect>,
+        /// breaks when thrown, doing its effects to everyone around
+        #[serde(default)]
+        shatters: bool,
+    },
+    /// equips or unequips the item
+    Equip,
+    /// unlocks a locked door when the player walks into it, and is used up
+    Key,
+    /// fired from a launcher that takes this kind of ammunition
+    Ammo { kind: AmmoKind },
+}
+
+impl Item {
+    /// What the player has to pick before this item can be used, if anything.
+    pub fn targeting(&self) -> Option<Targeting> {
+        match *self {
+            Item::Magic { aim: Aim::Bolt { range }, .. } => {
+                Some(Targeting::Monster { max_range: Some(range as f32) })
+            }
+            Item::Magic { aim: Aim::Ball { radius }, .. } => Some(Targeting::Tile {
+                max_range: None,
+                radius,
+            }),
+            _ => None,
+        }
+    }
+
+    /// What it does, as told to the player
+    pub fn description(&self) -> String {
+        match self {
+            Item::Magic { aim, effects, .. } => magic_description(*aim, effects),
+            Item::Equip => "can be equipped".into(),
+            Item::Key => "unlocks a locked door".into(),
+            Item::Ammo { kind } => format!("can be fired from a {}", kind.launcher()),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Targeting {
+    /// a visible monster, optionally within range of the player
+    Monster { max_range: Option<f32> },
+    /// any visible tile, optionally within range of the player, hitting
+    /// everything within `radius` of it
+    Tile { max_range: Option<f32>, radius: i32 },
 }
 
 pub enum UseResult {
@@ -387,13 +544,42 @@ pub enum UseResult {
 /// An object that can be equipped, yielding bonuses.
 pub struct Equipment {
     pub slot: Slot,
+    #[serde(default)]
     pub equipped: bool,
     pub power_bonus: i32,
     pub defense_bonus: i32,
     pub max_hp_bonus: i32,
+    /// light given off while equipped
+    #[serde(default)]
+    pub light: Option<Light>,
+    /// what it shoots, for bows and the like
+    #[serde(default)]
+    pub ranged: Option<Ranged>,
 }
 
 impl Equipment {
+    /// Where it is worn and what it gives, as told to the player
+    pub fn description(&self) -> String {
+        let mut parts = vec![format!("worn on {}", self.slot)];
+        let bonuses = [
+            (self.power_bonus, "power"),
+            (self.defense_bonus, "defense"),
+            (self.max_hp_bonus, "max hp"),
+        ];
+        for &(bonus, stat) in bonuses.iter().filter(|&&(bonus, _)| bonus != 0) {
+            parts.push(format!("{:+} {}", bonus, stat));
+        }
+        if let Some(light) = self.light {
+            parts.push(format!("light radius {}", light.radius));
+        }
+        if let Some(ranged) = self.ranged {
+            parts.push(ranged.description());
+        }
+        if self.equipped {
+            parts.push("equipped".into());
+        }
+        parts.join(", ")
+    }
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
@@ -401,6 +587,7 @@ pub enum Slot {
     LeftHand,
     RightHand,
     Head,
+    Belt,
 }
 
 impl std::fmt::Display for Slot {
@@ -409,6 +596,7 @@ impl std::fmt::Display for Slot {
             Slot::LeftHand => write!(f, "left hand"),
             Slot::RightHand => write!(f, "right hand"),
             Slot::Head => write!(f, "head"),
+            Slot::Belt => write!(f, "belt"),
         }
     }
 }
@@ -0,0 +1,111 @@
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+
+use super::*;
+
+/// A tile waiting to be searched, ordered so the heap hands out the one with
+/// the lowest estimated total cost first
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct Node {
+    estimate: i32,
+    cost: i32,
+    pos: (i32, i32),
+}
+
+impl Ord for Node {
+    fn cmp(&self, other: &Node) -> Ordering {
+        other
+            .estimate
+            .cmp(&self.estimate)
+            .then_with(|| other.cost.cmp(&self.cost))
+            .then_with(|| self.pos.cmp(&other.pos))
+    }
+}
+
+impl PartialOrd for Node {
+    fn partial_cmp(&self, other: &Node) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Find the cheapest way from `from` to `to` with A*, moving in all eight
+/// directions. Walls, locked doors and dangerous terrain are impassable,
+/// while slow terrain, closed doors and tiles with someone standing on them
+/// cost extra, so monsters go around each other when there is room to.
+/// Gives up after searching `max_nodes` tiles. The path leaves out the
+/// start tile and ends on the goal.
+pub fn find_path(
+    from: (i32, i32),
+    to: (i32, i32),
+    map: &Map,
+    objects: &[Object],
+    max_nodes: usize,
+) -> Option<Vec<(i32, i32)>> {
+    let index = |(x, y): (i32, i32)| (x * MAP_HEIGHT + y) as usize;
+    let heuristic = |(x, y): (i32, i32)| cmp::max((x - to.0).abs(), (y - to.1).abs());
+
+    let mut best_cost = vec![i32::MAX; (MAP_WIDTH * MAP_HEIGHT) as usize];
+    let mut came_from = vec![None; (MAP_WIDTH * MAP_HEIGHT) as usize];
+    let mut open = BinaryHeap::new();
+    best_cost[index(from)] = 0;
+    open.push(Node { estimate: heuristic(from), cost: 0, pos: from });
+
+    let mut searched = 0;
+    while let Some(Node { cost, pos, .. }) = open.pop() {
+        if pos == to {
+            let mut path = vec![to];
+            let mut current = to;
+            while let Some(previous) = came_from[index(current)] {
+                if previous == from {
+                    break;
+                }
+                path.push(previous);
+                current = previous;
+            }
+            path.reverse();
+            return Some(path);
+        }
+        // a stale entry for a tile that was reached more cheaply since
+        if cost > best_cost[index(pos)] {
+            continue;
+        }
+        searched += 1;
+        if searched > max_nodes {
+            return None;
+        }
+
+        for dx in -1..=1 {
+            for dy in -1..=1 {
+                let next = (pos.0 + dx, pos.1 + dy);
+                if (dx, dy) == (0, 0)
+                    || next.0 < 0
+                    || next.1 < 0
+                    || next.0 >= MAP_WIDTH
+                    || next.1 >= MAP_HEIGHT
+                    || !map[next.0 as usize][next.1 as usize].terrain.walkable()
+                    || (next != to && map[next.0 as usize][next.1 as usize].terrain.dangerous())
+                {
+                    continue;
+                }
+                let occupied = next != to
+                    && objects.iter().any(|object| object.blocks && object.pos() == next);
+                // slow terrain counts as a step for every full move it costs
+                let terrain = map[next.0 as usize][next.1 as usize].terrain;
+                let step = (terrain.move_cost() + 99) / 100;
+                // opening a door is worth another step
+                let door = if terrain == Terrain::ClosedDoor { 1 } else { 0 };
+                let next_cost = cost + door + if occupied { PATH_OCCUPIED_COST } else { step };
+                if next_cost < best_cost[index(next)] {
+                    best_cost[index(next)] = next_cost;
+                    came_from[index(next)] = Some(pos);
+                    open.push(Node {
+                        estimate: next_cost + heuristic(next),
+                        cost: next_cost,
+                        pos: next,
+                    });
+                }
+            }
+        }
+    }
+    None
+}
@@ -0,0 +1,75 @@
+use tcod::line::Line;
+
+use super::*;
+
+/// The way something fired or thrown went across the map, and who it hit
+#[derive(Clone, Debug, PartialEq)]
+pub struct Flight {
+    /// every tile it passed through, in order, ending where it came down
+    pub path: Vec<(i32, i32)>,
+    /// whoever was standing in the way
+    pub hit: Option<usize>,
+}
+
+impl Flight {
+    /// Where it came down, which is `from` if it didn't get anywhere
+    pub fn landing(&self, from: (i32, i32)) -> (i32, i32) {
+        *self.path.last().unwrap_or(&from)
+    }
+}
+
+/// How many tiles a straight line between two points crosses
+pub fn line_steps(from: (i32, i32), to: (i32, i32)) -> i32 {
+    cmp::max((to.0 - from.0).abs(), (to.1 - from.1).abs())
+}
+
+/// Follow a projectile along a straight line from `from` through `to`, and
+/// on past it, for at most `range` tiles. It stops short of the first wall
+/// or closed door, and at the first blocking object in its way, which it
+/// hits whether or not that was what it was aimed at.
+pub fn fly(from: (i32, i32), to: (i32, i32), range: i32, map: &Map, objects: &[Object]) -> Flight {
+    let mut flight = Flight { path: vec![], hit: None };
+    let steps = line_steps(from, to);
+    if steps == 0 {
+        return flight;
+    }
+    // aim as far past the target as it could go, along the same line
+    let scale = range / steps + 1;
+    let far = (
+        from.0 + (to.0 - from.0) * scale,
+        from.1 + (to.1 - from.1) * scale,
+    );
+    for (x, y) in Line::new(from, far).take(cmp::max(range, 0) as usize) {
+        let on_map = x >= 0 && y >= 0 && x < MAP_WIDTH && y < MAP_HEIGHT;
+        if !on_map || map[x as usize][y as usize].terrain.blocks() {
+            break;
+        }
+        flight.path.push((x, y));
+        flight.hit = objects
+            .iter()
+            .position(|object| object.blocks && object.pos() == (x, y));
+        if flight.hit.is_some() {
+            break;
+        }
+    }
+    flight
+}
+
+/// Fire a projectile drawn as `glyph`, telling the front-end to show it
+/// flying. Every ranged effect goes through here.
+pub fn fire(
+    from: (i32, i32),
+    to: (i32, i32),
+    range: i32,
+    (glyph, color): (char, Color),
+    objects: &[Object],
+    game: &mut Game,
+) -> Flight {
+    let flight = fly(from, to, range, &game.map, objects);
+    game.events.push(GameEvent::Projectile {
+        path: flight.path.clone(),
+        glyph,
+        color,
+    });
+    flight
+}
@@ -0,0 +1,211 @@
+use rand::Rng;
+use tcod::colors;
+
+use super::*;
+
+/// What a launcher shoots, and what ammunition items are fired from
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AmmoKind {
+    Arrow,
+    Bolt,
+    Stone,
+}
+
+impl AmmoKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            AmmoKind::Arrow => "arrows",
+            AmmoKind::Bolt => "bolts",
+            AmmoKind::Stone => "stones",
+        }
+    }
+
+    /// What this kind of ammunition is fired from
+    pub fn launcher(self) -> &'static str {
+        match self {
+            AmmoKind::Arrow => "bow",
+            AmmoKind::Bolt => "crossbow",
+            AmmoKind::Stone => "sling",
+        }
+    }
+
+    /// How it is drawn while in flight
+    pub fn projectile(self) -> (char, Color) {
+        match self {
+            AmmoKind::Arrow => ('-', colors::LIGHT_SEPIA),
+            AmmoKind::Bolt => ('-', colors::LIGHT_GREY),
+            AmmoKind::Stone => ('*', colors::GREY),
+        }
+    }
+}
+
+/// A launcher, or a monster's knack for shooting
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Ranged {
+    /// how many tiles a shot flies
+    pub range: i32,
+    /// rolled for the damage of a hit
+    pub damage: Dice,
+    pub ammo: AmmoKind,
+}
+
+impl Ranged {
+    /// What it shoots, as told to the player
+    pub fn description(&self) -> String {
+        format!(
+            "fires {} up to {} tiles for {} damage",
+            self.ammo.name(),
+            self.range,
+            self.damage
+        )
+    }
+}
+
+impl Object {
+    /// Whether picking this item up adds to `other`'s stack
+    pub fn stacks_with(&self, other: &Object) -> bool {
+        let is_ammo = |object: &Object| matches!(object.item, Some(Item::Ammo { .. }));
+        is_ammo(self) && is_ammo(other) && self.name == other.name
+    }
+
+    /// The name with how many there are, e.g. "an arrow" or "12 arrows"
+    pub fn stack_name(&self) -> String {
+        if self.count > 1 {
+            format!("{} {}s", self.count, self.name)
+        } else if self.name.starts_with(|c: char| "aeiouAEIOU".contains(c)) {
+            format!("an {}", self.name)
+        } else {
+            format!("a {}", self.name)
+        }
+    }
+}
+
+/// The launcher the player has equipped, if any
+pub fn equipped_launcher(inventory: &[Object]) -> Option<Ranged> {
+    inventory
+        .iter()
+        .filter_map(|item| item.equipment)
+        .filter(|equipment| equipment.equipped)
+        .filter_map(|equipment| equipment.ranged)
+        .next()
+}
+
+/// The equipped launcher and where in the inventory its ammunition is, or
+/// what the player is told if they can't shoot
+pub fn ready_to_fire(inventory: &[Object]) -> Result<(Ranged, usize), String> {
+    let launcher = equipped_launcher(inventory).ok_or("You have nothing to fire with.")?;
+    let kind = launcher.ammo;
+    let ammo_id = inventory
+        .iter()
+        .position(|item| item.item == Some(Item::Ammo { kind }))
+        .ok_or_else(|| format!("You have no {} for your {}.", kind.name(), kind.launcher()))?;
+    Ok((launcher, ammo_id))
+}
+
+/// Take one off an inventory stack, removing the stack if it was the last
+pub fn take_one(inventory_id: usize, inventory: &mut Vec<Object>) -> Object {
+    let stack = &mut inventory[inventory_id];
+    if stack.count <= 1 {
+        return inventory.remove(inventory_id);
+    }
+    stack.count -= 1;
+    let mut one = Object::new(0, 0, stack.char, stack.color, &stack.name, false);
+    one.item = stack.item.clone();
+    one.weight = stack.weight;
+    one
+}
+
+/// Let something fired or thrown come down where it stopped, to be picked
+/// up again, unless it fell into lava or a chasm
+pub fn land(mut item: Object, (x, y): (i32, i32), objects: &mut Vec<Object>, game: &Game) {
+    if game.map[x as usize][y as usize].terrain.dangerous() {
+        return;
+    }
+    let pile = objects
+        .iter()
+        .position(|object| object.pos() == (x, y) && object.stacks_with(&item));
+    match pile {
+        Some(pile) => objects[pile].count += item.count,
+        None => {
+            item.set_pos(x, y);
+            objects.push(item);
+        }
+    }
+}
+
+/// Shoot the equipped launcher at a tile. Returns whether a shot was taken.
+pub fn player_fire(target: (i32, i32), objects: &mut Vec<Object>, game: &mut Game) -> bool {
+    let (launcher, ammo_id) = match ready_to_fire(&game.inventory) {
+        Ok(ready) => ready,
+        Err(problem) => {
+            game.log.add(problem, colors::LIGHT_GREY, MessageCategory::Items);
+            return false;
+        }
+    };
+    let shot = take_one(ammo_id, &mut game.inventory);
+    let from = objects[PLAYER].pos();
+    let flight = fire(from, target, launcher.range, launcher.ammo.projectile(), objects, game);
+    match flight.hit {
+        Some(target_id) => {
+            let strike = objects[PLAYER].ranged_strike(launcher.damage);
+            attack(PLAYER, target_id, strike, "shoots", objects, game);
+        }
+        None => game.log.add(
+            format!("The {} hits nothing.", shot.name),
+            colors::LIGHT_GREY,
+            MessageCategory::Combat,
+        ),
+    }
+    if game.rng.gen_range(0, 100) >= AMMO_BREAK_PERCENT {
+        land(shot, flight.landing(from), objects, game);
+    }
+    true
+}
+
+/// An archer shoots at the player whenever it has a clear shot, backing
+/// away first if they got too close. Otherwise, or when it loses sight of
+/// them, it acts like a basic monster.
+pub fn ai_archer(monster_id: usize, weapon: Ranged, game: &mut Game, objects: &mut [Object]) -> Ai {
+    let player_alive = objects[PLAYER].fighter.is_some_and(|f| f.hp > 0);
+    if player_alive && monster_sees_player(monster_id, objects, &game.map) {
+        let (player_x, player_y) = objects[PLAYER].pos();
+        objects[monster_id].ai_state = AiState::Hunt { x: player_x, y: player_y };
+        let from = objects[monster_id].pos();
+        let steps = line_steps(from, (player_x, player_y));
+        if steps < ARCHER_DISTANCE && back_away(monster_id, game, objects) {
+            return Ai::Archer(weapon);
+        }
+        let in_the_way = || fly(from, (player_x, player_y), weapon.range, &game.map, objects).hit;
+        let clear_shot = steps > 1 && steps <= weapon.range && in_the_way() == Some(PLAYER);
+        if clear_shot {
+            let projectile = weapon.ammo.projectile();
+            fire(from, (player_x, player_y), weapon.range, projectile, objects, game);
+            let strike = objects[monster_id].ranged_strike(weapon.damage);
+            attack(monster_id, PLAYER, strike, "shoots", objects, game);
+            return Ai::Archer(weapon);
+        }
+    }
+    ai_basic(monster_id, game, objects);
+    Ai::Archer(weapon)
+}
+
+/// Step to the free neighbouring tile furthest from the player, if that
+/// gets further away. Returns whether it moved.
+fn back_away(monster_id: usize, game: &Game, objects: &mut [Object]) -> bool {
+    let (x, y) = objects[monster_id].pos();
+    let player_pos = objects[PLAYER].pos();
+    let best = (-1..=1)
+        .flat_map(|dx| (-1..=1).map(move |dy| (x + dx, y + dy)))
+        .filter(|&(x, y)| {
+            !is_blocked(x, y, &game.map, objects)
+                && !game.map[x as usize][y as usize].terrain.dangerous()
+        })
+        .max_by_key(|&pos| line_steps(pos, player_pos));
+    match best {
+        Some(pos) if line_steps(pos, player_pos) > line_steps((x, y), player_pos) => {
+            objects[monster_id].set_pos(pos.0, pos.1);
+            true
+        }
+        _ => false,
+    }
+}
@@ -0,0 +1,76 @@
+use std::io::{ BufRead, BufReader, Write };
+use std::fs::File;
+use std::error::Error;
+
+use super::*;
+
+/// A recorded run: the seed it started from and every command issued since.
+/// Feeding the commands to `World::new(seed)` reproduces the run exactly.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Replay {
+    pub seed: u64,
+    pub commands: Vec<Command>,
+}
+
+/// Writes commands to the replay file as they are issued, so the file is
+/// complete even if the game crashes.
+pub struct Recorder {
+    file: File,
+}
+
+impl Recorder {
+    /// Start the replay file over for this world. A world that has already
+    /// seen commands, e.g. one loaded from a save, keeps those from the old
+    /// file, dropping any issued after it was saved.
+    pub fn start(world: &World) -> Result<Self, Box<dyn Error>> {
+        Recorder::start_at("replay", world)
+    }
+
+    /// Like `start`, with the replay file at `path`
+    pub fn start_at(path: &str, world: &World) -> Result<Self, Box<dyn Error>> {
+        let issued = world.game.commands_issued;
+        let earlier = if issued == 0 {
+            vec![]
+        } else {
+            let replay = read_replay(path)?;
+            if replay.seed != world.game.seed || replay.commands.len() < issued {
+                return Err("the replay file is from another game".into());
+            }
+            replay.commands[..issued].to_vec()
+        };
+        let mut recorder = Recorder {
+            file: File::create(path)?,
+        };
+        writeln!(recorder.file, "{}", serde_json::to_string(&world.game.seed)?)?;
+        for command in earlier {
+            recorder.record(command)?;
+        }
+        Ok(recorder)
+    }
+
+    pub fn record(&mut self, command: Command) -> Result<(), Box<dyn Error>> {
+        writeln!(self.file, "{}", serde_json::to_string(&command)?)?;
+        self.file.flush()?;
+        Ok(())
+    }
+}
+
+/// Read the replay file: the seed on the first line, then one command per line
+pub fn load_replay() -> Result<Replay, Box<dyn Error>> {
+    read_replay("replay")
+}
+
+/// Read a replay file kept at `path`
+pub fn read_replay(path: &str) -> Result<Replay, Box<dyn Error>> {
+    let file = File::open(path)?;
+    let mut lines = BufReader::new(file).lines();
+    let seed = match lines.next() {
+        Some(line) => serde_json::from_str(&line?)?,
+        None => return Err("replay file is empty".into()),
+    };
+    let mut commands = vec![];
+    for line in lines {
+        commands.push(serde_json::from_str(&line?)?);
+    }
+    Ok(Replay { seed, commands })
+}
@@ -0,0 +1,162 @@
+use tcod::colors;
+
+use super::*;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum StatusKind {
+    /// loses `potency` hit points every tick
+    Poison,
+    /// gains `potency` hit points every tick
+    Regeneration,
+    /// stumbles around in random directions
+    Confusion,
+    /// cannot act at all
+    Stun,
+    /// twice as fast
+    Haste,
+    /// half as fast
+    Slow,
+    /// can only see adjacent tiles
+    Blindness,
+    /// cannot be seen from further than an adjacent tile
+    Invisibility,
+}
+
+/// How a new effect combines with an active one of the same kind
+enum Stacking {
+    /// potencies add up, the longer duration wins
+    Intensity,
+    /// durations add up
+    Duration,
+    /// the longer duration wins
+    Refresh,
+}
+
+impl StatusKind {
+    fn stacking(self) -> Stacking {
+        use StatusKind::*;
+        match self {
+            Poison | Regeneration => Stacking::Intensity,
+            Confusion | Stun | Blindness | Invisibility => Stacking::Duration,
+            Haste | Slow => Stacking::Refresh,
+        }
+    }
+
+    /// The effect this one cancels out when applied
+    fn opposite(self) -> Option<StatusKind> {
+        match self {
+            StatusKind::Haste => Some(StatusKind::Slow),
+            StatusKind::Slow => Some(StatusKind::Haste),
+            _ => None,
+        }
+    }
+
+    /// Describes something under this effect, e.g. "Orc is confused"
+    pub fn adjective(self) -> &'static str {
+        use StatusKind::*;
+        match self {
+            Poison => "poisoned",
+            Regeneration => "regenerating",
+            Confusion => "confused",
+            Stun => "stunned",
+            Haste => "hasted",
+            Slow => "slowed",
+            Blindness => "blind",
+            Invisibility => "invisible",
+        }
+    }
+}
+
+/// A timed effect on an object, counted down once per scheduler tick.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct StatusEffect {
+    pub kind: StatusKind,
+    pub turns: i32,
+    /// strength of effects that have one, like poison damage per tick
+    pub potency: i32,
+}
+
+impl Object {
+    pub fn has_status(&self, kind: StatusKind) -> bool {
+        self.effects.iter().any(|effect| effect.kind == kind)
+    }
+
+    /// Add an effect, combining it with any active one of the same kind
+    pub fn add_status(&mut self, new: StatusEffect) {
+        if let Some(opposite) = new.kind.opposite() {
+            self.effects.retain(|effect| effect.kind != opposite);
+        }
+        match self.effects.iter_mut().find(|effect| effect.kind == new.kind) {
+            Some(effect) => match new.kind.stacking() {
+                Stacking::Intensity => {
+                    effect.potency += new.potency;
+                    effect.turns = cmp::max(effect.turns, new.turns);
+                }
+                Stacking::Duration => effect.turns += new.turns,
+                Stacking::Refresh => effect.turns = cmp::max(effect.turns, new.turns),
+            },
+            None => self.effects.push(new),
+        }
+    }
+
+    /// Invisible objects can only be made out from an adjacent tile
+    pub fn hidden_from(&self, viewer: &Object) -> bool {
+        self.has_status(StatusKind::Invisibility) && self.distance_to(viewer) >= 2.0
+    }
+
+    /// How this object is referred to at the start of a sentence
+    pub fn subject(&self) -> String {
+        if self.name == "player" {
+            "You".into()
+        } else {
+            self.name.clone()
+        }
+    }
+}
+
+/// Run every object's status effects for one scheduler tick, expiring the
+/// ones that ran out
+pub fn tick_status_effects(objects: &mut [Object], game: &mut Game) {
+    for (id, object) in objects.iter_mut().enumerate() {
+        if !object.alive || object.effects.is_empty() {
+            continue;
+        }
+
+        for effect in object.effects.clone() {
+            match effect.kind {
+                StatusKind::Poison => {
+                    let died = object.take_damage(effect.potency, game).is_some();
+                    if died {
+                        game.events.push(GameEvent::Died { id: object.id });
+                    }
+                }
+                StatusKind::Regeneration => object.heal(effect.potency, game),
+                _ => {}
+            }
+        }
+
+        for effect in object.effects.iter_mut() {
+            effect.turns -= 1;
+        }
+        let expired: Vec<StatusEffect> = object
+            .effects
+            .iter()
+            .filter(|effect| effect.turns <= 0)
+            .cloned()
+            .collect();
+        object.effects.retain(|effect| effect.turns > 0);
+        if object.alive {
+            let verb = if id == PLAYER { "are" } else { "is" };
+            for effect in expired {
+                game.log.add_entry(
+                    Message::new(
+                        format!("{} {} no longer {}.", object.subject(), verb, effect.kind.adjective()),
+                        colors::LIGHT_GREY,
+                        MessageCategory::Combat,
+                    )
+                    .by(object.id),
+                );
+            }
+        }
+    }
+}
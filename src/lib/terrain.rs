@@ -0,0 +1,120 @@
+use super::*;
+
+/// What a map tile is made of, which decides how it looks, whether it can be
+/// walked through or seen past, and what happens to whoever steps onto it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Terrain {
+    Wall,
+    Floor,
+    OpenDoor,
+    ClosedDoor,
+    LockedDoor,
+    ShallowWater,
+    DeepWater,
+    Lava,
+    Chasm,
+    Rubble,
+    Grass,
+}
+
+impl Terrain {
+    /// Whether nothing can walk onto it
+    pub fn blocks(self) -> bool {
+        use Terrain::*;
+        matches!(self, Wall | ClosedDoor | LockedDoor)
+    }
+
+    /// Whether it can be got through on foot, opening a door if need be
+    pub fn walkable(self) -> bool {
+        !self.blocks() || self == Terrain::ClosedDoor
+    }
+
+    pub fn blocks_sight(self) -> bool {
+        use Terrain::*;
+        matches!(self, Wall | ClosedDoor | LockedDoor)
+    }
+
+    /// Energy it takes to step onto it, as a percentage of ACTION_COST
+    pub fn move_cost(self) -> i32 {
+        use Terrain::*;
+        match self {
+            ShallowWater | Rubble => 150,
+            DeepWater => 300,
+            _ => 100,
+        }
+    }
+
+    pub fn is_door(self) -> bool {
+        use Terrain::*;
+        matches!(self, OpenDoor | ClosedDoor | LockedDoor)
+    }
+
+    /// Whether monsters keep off it unless there is no other way
+    pub fn dangerous(self) -> bool {
+        self == Terrain::Lava || self == Terrain::Chasm
+    }
+
+    /// Character drawn on the tile, if any
+    pub fn glyph(self) -> Option<char> {
+        use Terrain::*;
+        match self {
+            Wall | Floor => None,
+            OpenDoor => Some('\''),
+            ClosedDoor | LockedDoor => Some('+'),
+            ShallowWater | DeepWater | Lava => Some('~'),
+            Chasm => Some(' '),
+            Rubble => Some(':'),
+            Grass => Some('"'),
+        }
+    }
+
+    pub fn color(self) -> Color {
+        use Terrain::*;
+        match self {
+            OpenDoor | ClosedDoor => colors::DARKER_ORANGE,
+            LockedDoor => colors::DARKER_RED,
+            ShallowWater => colors::LIGHT_BLUE,
+            DeepWater => colors::BLUE,
+            Lava => colors::YELLOW,
+            Rubble => colors::GREY,
+            Grass => colors::GREEN,
+            Wall | Floor | Chasm => colors::WHITE,
+        }
+    }
+
+    /// Background color when lit, and when only remembered
+    pub fn background(self, lit: bool) -> Color {
+        use Terrain::*;
+        let (light, dark) = match self {
+            Wall | ClosedDoor | LockedDoor => (COLOR_LIGHT_WALL, COLOR_DARK_WALL),
+            ShallowWater => (colors::DARK_BLUE, colors::DARKEST_BLUE),
+            DeepWater => (colors::DARKER_BLUE, colors::DARKEST_BLUE),
+            Lava => (colors::ORANGE, colors::DARKER_ORANGE),
+            Chasm => (colors::BLACK, colors::BLACK),
+            Floor | OpenDoor | Rubble | Grass => (COLOR_LIGHT_GROUND, COLOR_DARK_GROUND),
+        };
+        if lit {
+            light
+        } else {
+            dark
+        }
+    }
+
+    /// How the terrain is called when looked at
+    pub fn name(self) -> &'static str {
+        use Terrain::*;
+        match self {
+            Wall => "wall",
+            Floor => "floor",
+            OpenDoor => "open door",
+            ClosedDoor => "closed door",
+            LockedDoor => "locked door",
+            ShallowWater => "shallow water",
+            DeepWater => "deep water",
+            Lava => "lava",
+            Chasm => "chasm",
+            Rubble => "rubble",
+            Grass => "grass",
+        }
+    }
+}
@@ -0,0 +1,84 @@
+use tcod::colors;
+
+use super::*;
+
+impl Item {
+    /// Whether it breaks when thrown, splashing what it does over everyone
+    /// around where it lands
+    pub fn shatters(&self) -> bool {
+        matches!(self, Item::Magic { shatters: true, .. })
+    }
+}
+
+/// How many tiles something this heavy can be thrown
+pub fn throw_range(weight: i32) -> i32 {
+    cmp::max(THROW_MIN_RANGE, THROW_RANGE - weight / 2)
+}
+
+/// Throw one of an inventory item towards a tile. It flies at the first
+/// thing in its way, hitting as hard as it is heavy, and comes down where
+/// it stopped, unless it is a potion, which shatters there instead.
+pub fn throw_item(
+    inventory_id: usize,
+    target: (i32, i32),
+    objects: &mut Vec<Object>,
+    game: &mut Game,
+) {
+    if game.inventory[inventory_id].equipment.is_some_and(|e| e.equipped) {
+        game.inventory[inventory_id].unequip(&mut game.log);
+    }
+    let item = take_one(inventory_id, &mut game.inventory);
+    game.log.add(
+        format!("You throw {}.", item.stack_name()),
+        colors::WHITE,
+        MessageCategory::Items,
+    );
+    let from = objects[PLAYER].pos();
+    let range = throw_range(item.weight);
+    let flight = fire(from, target, range, (item.char, item.color), objects, game);
+    let landing = flight.landing(from);
+    match &item.item {
+        Some(Item::Magic { effects, shatters: true, .. }) => {
+            shatter(&item.name, effects, landing, objects, game)
+        }
+        _ => {
+            if let Some(target_id) = flight.hit {
+                let strike = objects[PLAYER].ranged_strike(Dice::flat(item.weight));
+                attack(PLAYER, target_id, strike, "hits", objects, game);
+            }
+            land(item, landing, objects, game);
+        }
+    }
+}
+
+/// Break a potion at a tile, giving everyone within SHATTER_RADIUS of it
+/// what drinking it would
+fn shatter(
+    name: &str,
+    effects: &[Effect],
+    (x, y): (i32, i32),
+    objects: &mut [Object],
+    game: &mut Game,
+) {
+    game.log.add(
+        format!("The {} shatters!", name),
+        colors::LIGHT_BLUE,
+        MessageCategory::Combat,
+    );
+    let splashed: Vec<_> = (0..objects.len())
+        .filter(|&id| {
+            objects[id].alive
+                && objects[id].fighter.is_some()
+                && objects[id].distance(x, y) <= SHATTER_RADIUS as f32
+        })
+        .collect();
+    for id in splashed {
+        for &effect in effects {
+            apply_effect(effect, id, objects, game);
+        }
+        // a monster caught in the splash knows who threw it
+        if id != PLAYER && objects[id].ai.is_some() {
+            objects[id].ai_state = AiState::Hunt { x: objects[PLAYER].x, y: objects[PLAYER].y };
+        }
+    }
+}
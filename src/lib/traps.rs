@@ -0,0 +1,119 @@
+use rand::Rng;
+use rand::distributions::{Weighted, WeightedChoice, IndependentSample};
+use tcod::colors;
+
+use super::*;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TrapKind {
+    /// shoots a poisoned dart
+    Dart,
+    /// sends whoever steps on it somewhere else on the level
+    Teleport,
+    /// wakes up every monster on the level
+    Alarm,
+    /// drops whoever steps on it to the level below
+    Pit,
+    /// lets out a cloud of confusing gas
+    Gas,
+}
+
+impl TrapKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            TrapKind::Dart => "dart trap",
+            TrapKind::Teleport => "teleport trap",
+            TrapKind::Alarm => "alarm trap",
+            TrapKind::Pit => "pit trap",
+            TrapKind::Gas => "gas trap",
+        }
+    }
+}
+
+/// A trap lying on the floor, set off by anyone who walks onto it
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Trap {
+    pub kind: TrapKind,
+    /// not drawn, and not known to the player, until found
+    pub hidden: bool,
+}
+
+impl Object {
+    /// A trap the player doesn't know about, and so must not be shown
+    pub fn is_hidden_trap(&self) -> bool {
+        self.trap.is_some_and(|trap| trap.hidden)
+    }
+}
+
+/// Create a hidden trap
+pub fn trap_object(kind: TrapKind, x: i32, y: i32) -> Object {
+    let mut trap = Object::new(x, y, '^', colors::LIGHT_RED, kind.name(), false);
+    trap.trap = Some(Trap { kind, hidden: true });
+    trap
+}
+
+/// Hide a few traps in an area of floor
+pub fn place_traps(area: &[(i32, i32)], objects: &mut Vec<Object>, level: u32, rng: &mut GameRng) {
+    let max_traps = from_dungeon_level(
+        &[
+            Transition { level: 1, value: 1 },
+            Transition { level: 5, value: 2 },
+        ],
+        level,
+    );
+
+    let trap_chances = &mut [
+        (TrapKind::Dart, from_dungeon_level(&[Transition { level: 1, value: 30 }], level)),
+        (TrapKind::Alarm, from_dungeon_level(&[Transition { level: 1, value: 20 }], level)),
+        (TrapKind::Teleport, from_dungeon_level(&[Transition { level: 3, value: 15 }], level)),
+        (TrapKind::Gas, from_dungeon_level(&[Transition { level: 3, value: 15 }], level)),
+        (TrapKind::Pit, from_dungeon_level(&[Transition { level: 4, value: 10 }], level)),
+    ]
+    .iter()
+    .map(|&(kind, weight)| Weighted { weight, item: kind })
+    .filter(|chance| chance.weight > 0)
+    .collect::<Vec<_>>();
+    let trap_choice = WeightedChoice::new(trap_chances);
+
+    // most areas have no trap at all
+    for _ in 0..max_traps {
+        if rng.gen_range(0, 100) >= TRAP_PERCENT {
+            continue;
+        }
+        let (x, y) = area[rng.gen_range(0, area.len())];
+        if !objects.iter().any(|object| object.pos() == (x, y)) {
+            objects.push(trap_object(trap_choice.ind_sample(rng), x, y));
+        }
+    }
+}
+
+/// Roll to notice each hidden trap within `radius` of the player. Returns
+/// how many were found.
+pub fn find_traps(radius: f32, percent: u32, objects: &mut [Object], game: &mut Game) -> usize {
+    // experienced adventurers know what to look for
+    let chance = percent + 5 * objects[PLAYER].level as u32;
+    let mut found = 0;
+    for id in 0..objects.len() {
+        if !objects[id].is_hidden_trap() || objects[PLAYER].distance_to(&objects[id]) > radius {
+            continue;
+        }
+        if game.rng.gen_range(0, 100) < chance {
+            reveal_trap(&mut objects[id]);
+            game.log.add(
+                format!("You find a {}!", objects[id].name),
+                colors::LIGHT_RED,
+                MessageCategory::System,
+            );
+            found += 1;
+        }
+    }
+    found
+}
+
+/// Make a trap known, and keep it on the map once seen
+pub fn reveal_trap(object: &mut Object) {
+    if let Some(trap) = object.trap.as_mut() {
+        trap.hidden = false;
+        object.always_visible = true;
+    }
+}
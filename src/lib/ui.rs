@@ -0,0 +1,1160 @@
+use tcod::input::{Mouse, Key};
+use tcod::colors::*;
+use tcod::input::{self, Event};
+
+use super::*;
+
+/// What the front-end should do after a key press.
+pub enum Input {
+    Act(Command),
+    Exit,
+    Idle,
+}
+
+/// `seed` fixes the dungeon of every new game; without it each one is random
+pub fn main_menu(tcod: &mut Tcod, seed: Option<u64>, catalog: &Catalog) {
+    let img = tcod::image::Image::from_file("menu_background.png")
+        .ok()
+        .expect("Background image not found");
+
+    while !tcod.root.window_closed() {
+        // Show bg image, at twice the regular console resolution
+        tcod::image::blit_2x(&img, (0, 0), (-1, -1), &mut tcod.root, (0, 0));
+
+        tcod.root.set_default_foreground(colors::LIGHT_YELLOW);
+        tcod.root.print_ex(
+            SCREEN_WIDTH / 2,
+            SCREEN_HEIGHT / 2 - 4,
+            BackgroundFlag::None,
+            TextAlignment::Center,
+            "TOMBS OF THE ANCIENT KINGS",
+        );
+        tcod.root.print_ex(
+            SCREEN_WIDTH / 2,
+            SCREEN_HEIGHT - 2,
+            BackgroundFlag::None,
+            TextAlignment::Center,
+            "By Yours Truly",
+        );
+
+        let choices = &["Play a new game", "Continue last game", "Watch last replay", "Quit"];
+        let choice = menu("", choices, 24, &mut tcod.root);
+
+        match choice {
+            Some(0) => {
+                let mut world = World::new(seed.unwrap_or_else(rand::random), catalog.clone());
+                play_game(&mut world, tcod);
+            }
+            Some(1) => {
+                match load_game(catalog.clone()) {
+                    Ok(mut world) => {
+                        play_game(&mut world, tcod);
+                    }
+                    Err(_e) => {
+                        msgbox("\nNo saved game to load.\n", 24, &mut tcod.root);
+                        continue;
+                    }
+                }
+            }
+            Some(2) => {
+                match load_replay() {
+                    Ok(replay) => {
+                        play_replay(&replay, catalog, tcod);
+                    }
+                    Err(_e) => {
+                        msgbox("\nNo replay to watch.\n", 24, &mut tcod.root);
+                        continue;
+                    }
+                }
+            }
+            Some(3) => {
+                break;
+            }
+            _ => {}
+        }
+    }
+}
+
+pub fn play_game(world: &mut World, tcod: &mut Tcod) {
+
+    let mut key = Default::default();
+
+    let mut recorder = match Recorder::start(world) {
+        Ok(recorder) => Some(recorder),
+        Err(e) => {
+            world.game.log.add(
+                format!("Not recording a replay: {}", e),
+                colors::RED,
+                MessageCategory::System,
+            );
+            None
+        }
+    };
+
+    while !tcod.root.window_closed() {
+        tcod.con.clear();
+
+        match input::check_for_event(input::MOUSE | input::KEY_PRESS) {
+            Some((_, Event::Mouse(m))) => tcod.mouse = m,
+            Some((_, Event::Key(k))) => key = k,
+            _ => key = Default::default(),
+        }
+
+        render_all(tcod, world);
+
+        tcod.root.flush();
+
+        if let Some(stat) = level_up(world, tcod) {
+            let command = Command::LevelUp(stat);
+            if let Some(recorder) = recorder.as_mut() {
+                recorder.record(command).ok();
+            }
+            world.step(command);
+        }
+
+        match handle_keys(key, tcod, world) {
+            Input::Exit => {
+                save_game(world).unwrap();
+                break;
+            }
+            Input::Act(command) => {
+                if let Some(recorder) = recorder.as_mut() {
+                    recorder.record(command).ok();
+                }
+                let events = world.step(command);
+                animate(tcod, world, &events);
+            }
+            Input::Idle => {}
+        }
+    }
+}
+
+/// Show anything that happened during a step that needs more than a single
+/// frame, like projectiles flying
+fn animate(tcod: &mut Tcod, world: &World, events: &[GameEvent]) {
+    for event in events {
+        if let GameEvent::Projectile { path, glyph, color } = event {
+            for &(x, y) in path {
+                // only the part the player can see is drawn
+                if !world.fov.is_in_fov(x, y) {
+                    continue;
+                }
+                render_all(tcod, world);
+                tcod.root.set_default_foreground(*color);
+                tcod.root.put_char(x, y, *glyph, BackgroundFlag::None);
+                tcod.root.flush();
+                std::thread::sleep(std::time::Duration::from_millis(PROJECTILE_FRAME_MS));
+            }
+        }
+    }
+}
+
+/// Play a recorded run back. Space pauses, the right arrow steps one command
+/// while paused, `f` toggles fast-forward and Escape leaves.
+pub fn play_replay(replay: &Replay, catalog: &Catalog, tcod: &mut Tcod) {
+    use tcod::input::KeyCode::*;
+
+    let mut world = World::new(replay.seed, catalog.clone());
+    let mut next_command = 0;
+    let mut paused = false;
+    let mut fast_forward = false;
+    let mut frame = 0;
+
+    while !tcod.root.window_closed() {
+        tcod.con.clear();
+
+        let mut step = false;
+        match input::check_for_event(input::MOUSE | input::KEY_PRESS) {
+            Some((_, Event::Mouse(m))) => tcod.mouse = m,
+            Some((_, Event::Key(key))) => match key {
+                Key { code: Escape, .. } => break,
+                Key { code: Spacebar, .. } => paused = !paused,
+                Key { code: Right, .. } => step = paused,
+                Key { printable: 'f', .. } => fast_forward = !fast_forward,
+                _ => {}
+            },
+            _ => {}
+        }
+
+        // when playing, take one command every few frames, or several every
+        // frame when fast-forwarding
+        frame += 1;
+        let commands_to_run = if step {
+            1
+        } else if paused {
+            0
+        } else if fast_forward {
+            REPLAY_FAST_COMMANDS_PER_FRAME
+        } else if frame % REPLAY_FRAMES_PER_COMMAND == 0 {
+            1
+        } else {
+            0
+        };
+        for _ in 0..commands_to_run {
+            if let Some(&command) = replay.commands.get(next_command) {
+                let events = world.step(command);
+                if !fast_forward {
+                    animate(tcod, &world, &events);
+                }
+                next_command += 1;
+            }
+        }
+
+        render_all(tcod, &world);
+
+        let status = if next_command == replay.commands.len() {
+            "finished"
+        } else if paused {
+            "paused"
+        } else if fast_forward {
+            "fast"
+        } else {
+            "playing"
+        };
+        tcod.root.set_default_foreground(colors::LIGHT_YELLOW);
+        tcod.root.print_ex(
+            1,
+            0,
+            BackgroundFlag::None,
+            TextAlignment::Left,
+            format!(
+                "Replay {}/{} ({}) - space: pause, right: step, f: fast, esc: leave",
+                next_command,
+                replay.commands.len(),
+                status
+            ),
+        );
+
+        tcod.root.flush();
+    }
+}
+
+pub fn handle_keys(
+    key: Key,
+    tcod: &mut Tcod,
+    world: &mut World,
+    ) -> Input {
+    use Input::*;
+    use tcod::input::KeyCode::*;
+
+    let player_alive = world.objects[PLAYER].alive;
+    match (key, player_alive) {
+        (
+        Key {
+            code: Enter,
+            alt: true,
+            ..
+        },
+        _,
+        ) => {
+            // alt+enter to toggle fullscreen
+            let fullscreen = tcod.root.is_fullscreen();
+            tcod.root.set_fullscreen(!fullscreen);
+            Idle
+        }
+        (Key { code: Escape, .. }, _) => Exit, //exit game
+
+        (Key { code: Up, .. }, true) | (Key { code: NumPad8, .. }, true) => {
+            Act(Command::Move(0, -1))
+        }
+
+        (Key { code: Down, .. }, true) | (Key { code: NumPad2, .. }, true) => {
+            Act(Command::Move(0, 1))
+        }
+
+        (Key { code: Left, .. }, true) | (Key { code: NumPad4, .. }, true) => {
+            Act(Command::Move(-1, 0))
+        }
+
+        (Key { code: Right, .. }, true) | (Key { code: NumPad6, .. }, true) => {
+            Act(Command::Move(1, 0))
+        }
+
+        (Key { code: Home, .. }, true) | (Key { code: NumPad7, .. }, true) => {
+            Act(Command::Move(-1, -1))
+        }
+
+        (Key { code: PageUp, .. }, true) | (Key { code: NumPad9, .. }, true) => {
+            Act(Command::Move(1, -1))
+        }
+
+        (Key { code: End, .. }, true) | (Key { code: NumPad1, .. }, true) => {
+            Act(Command::Move(-1, 1))
+        }
+
+        (Key { code: PageDown, .. }, true) | (Key { code: NumPad3, .. }, true) => {
+            Act(Command::Move(1, 1))
+        }
+
+        (Key { code: NumPad5, .. }, true)  => {
+            Act(Command::Wait)
+        }
+        (Key { printable: 'g', .. }, true) => {
+            //pick up item
+            Act(Command::PickUp)
+        }
+
+        (Key { printable: 'i', .. }, true) => {
+            // show the inventory
+            let inventory_index = inventory_menu(
+                &world.game.inventory,
+                "Press the key next to an item to use it, or any other to cancel. \n",
+                &mut tcod.root,
+            );
+
+            match inventory_index {
+                Some(inventory_id) => {
+                    let target = choose_target(inventory_id, tcod, world);
+                    let targeted = world.game.inventory[inventory_id]
+                        .item
+                        .as_ref()
+                        .and_then(Item::targeting)
+                        .is_some();
+                    // backing out of picking a target uses nothing up
+                    if targeted && target.is_none() {
+                        Idle
+                    } else {
+                        Act(Command::UseItem { inventory_id, target })
+                    }
+                }
+                None => Idle,
+            }
+        }
+        (Key { printable: 'd', .. }, true) => {
+            // show the inventory
+            let inventory_index = inventory_menu(
+                &world.game.inventory,
+                "Press the key next to an item to drop it, or any other to cancel. \n",
+                &mut tcod.root,
+            );
+
+            match inventory_index {
+                Some(inventory_index) => Act(Command::Drop(inventory_index)),
+                None => Idle,
+            }
+        }
+
+        (Key { printable: 't', .. }, true) => {
+            let inventory_index = inventory_menu(
+                &world.game.inventory,
+                "Press the key next to an item to throw it, or any other to cancel. \n",
+                &mut tcod.root,
+            );
+            match inventory_index {
+                Some(inventory_id) => {
+                    let item = &world.game.inventory[inventory_id];
+                    let radius = match &item.item {
+                        Some(effect) if effect.shatters() => SHATTER_RADIUS,
+                        _ => 0,
+                    };
+                    let targeting = Targeting::Tile {
+                        max_range: Some(throw_range(item.weight) as f32),
+                        radius,
+                    };
+                    world.game.log.add(
+                        "Pick where to throw it: move the cursor, Tab for the next enemy, \
+                         Enter to confirm, Escape to cancel.",
+                        colors::LIGHT_CYAN,
+                        MessageCategory::System,
+                    );
+                    match target_tile(tcod, world, targeting) {
+                        Some(target) => Act(Command::Throw { inventory_id, target }),
+                        None => Idle,
+                    }
+                }
+                None => Idle,
+            }
+        }
+
+        (Key { printable: '.', .. }, true) | (Key { printable: ',', .. }, true) => {
+            // go down or up the stairs, if the player is on them
+            Act(Command::TakeStairs)
+        }
+
+        (Key { printable: 'o', .. }, true) => {
+            // open or close a door, only asking which if there is more than one
+            let (x, y) = world.objects[PLAYER].pos();
+            let doors: Vec<_> = (-1..=1)
+                .flat_map(|dx| (-1..=1).map(move |dy| (dx, dy)))
+                .filter(|&(dx, dy)| world.game.map[(x + dx) as usize][(y + dy) as usize].terrain.is_door())
+                .collect();
+            let direction = if doors.len() == 1 {
+                Some(doors[0])
+            } else {
+                choose_direction(tcod, world, "Which door? Pick a direction, or Escape to cancel.")
+            };
+            match direction {
+                Some((dx, dy)) => Act(Command::Door(dx, dy)),
+                None => Idle,
+            }
+        }
+
+        (Key { printable: 's', .. }, true) => Act(Command::Search),
+
+        (Key { printable: 'f', .. }, true) => {
+            // shoot the equipped launcher, if there is anything to shoot
+            match ready_to_fire(&world.game.inventory) {
+                Ok((launcher, _)) => {
+                    world.game.log.add(
+                        "Pick what to shoot at: move the cursor, Tab for the next enemy, \
+                         Enter to confirm, Escape to cancel.",
+                        colors::LIGHT_CYAN,
+                        MessageCategory::System,
+                    );
+                    let targeting = Targeting::Tile {
+                        max_range: Some(launcher.range as f32),
+                        radius: 0,
+                    };
+                    match target_tile(tcod, world, targeting) {
+                        Some(target) => Act(Command::Fire { target }),
+                        None => Idle,
+                    }
+                }
+                Err(problem) => {
+                    world.game.log.add(problem, colors::LIGHT_GREY, MessageCategory::Items);
+                    Idle
+                }
+            }
+        }
+
+        (Key { printable: 'l', .. }, _) => {
+            look_mode(tcod, world);
+            Idle
+        }
+
+        (Key { printable: 'm', .. }, _) => {
+            message_history(tcod, world);
+            Idle
+        }
+
+        (Key { printable: 'c', .. }, true) => {
+            let player = &world.objects[PLAYER];
+            let level = player.level;
+            let level_up_xp = world.level_up_xp();
+            if let Some(fighter) = player.fighter.as_ref() {
+                // power adds to every roll, so it is shown as part of it
+                let damage = Dice {
+                    bonus: fighter.damage.bonus + player.power(&world.game),
+                    ..fighter.damage
+                };
+                let msg = format!(
+                    "Character information
+
+                    Level: {}
+                    Experience: {}
+                    Experience to next level: {}
+
+                    Max HP: {}
+                    Atk: {}
+                    Def: {}
+                    Accuracy: {:+}%
+                    Evasion: {:+}%
+                    Speed: {}
+
+                    Seed: {}",
+                    level, fighter.xp, level_up_xp, player.max_hp(&world.game),
+                    damage, player.defense(&world.game),
+                    fighter.accuracy, fighter.evasion, player.speed(), world.game.seed
+                );
+                msgbox(&msg, CHARACTER_SCREEN_WIDTH, &mut tcod.root);
+            }
+            Idle
+        }
+        
+        _ => Idle
+    }
+    
+}
+
+pub fn get_names_under_mouse(mouse: Mouse, objects: &[Object], fov_map: &Vision) -> String {
+    let (x, y) = (mouse.cx as i32, mouse.cy as i32);
+
+    // create a list with the names of all objects at the mouse's coordinates and in FOV,
+    // along with any status effects on them
+    let names = objects
+        .iter()
+        .filter(|obj| {
+            obj.pos() == (x, y)
+                && fov_map.is_in_fov(obj.x, obj.y)
+                && !obj.hidden_from(&objects[PLAYER])
+                && !obj.is_hidden_trap()
+        })
+        .map(|obj| {
+            if obj.effects.is_empty() {
+                obj.name.clone()
+            } else {
+                let effects: Vec<_> = obj.effects.iter().map(|e| e.kind.adjective()).collect();
+                format!("{} ({})", obj.name, effects.join(", "))
+            }
+        })
+        .collect::<Vec<_>>();
+
+    names.join(", ") // Join the names, separated by commas
+}
+
+/// What the player knows about a tile, a line each for its terrain and
+/// everything on it they can see (or remember, if it is out of sight)
+pub fn describe_tile(world: &World, x: i32, y: i32) -> Vec<String> {
+    let tile = world.game.map[x as usize][y as usize];
+    if !tile.explored {
+        return vec!["You haven't seen what is there.".into()];
+    }
+    let visible = world.fov.is_in_fov(x, y);
+    let terrain = tile.terrain.name();
+    let terrain = format!("{}{}", terrain[..1].to_uppercase(), &terrain[1..]);
+    let mut lines = vec![if visible { terrain } else { format!("{} (remembered)", terrain) }];
+
+    let player = &world.objects[PLAYER];
+    for (id, object) in world.objects.iter().enumerate() {
+        let seen = if visible {
+            !object.hidden_from(player) && !object.is_hidden_trap()
+        } else {
+            object.always_visible
+        };
+        if object.pos() == (x, y) && seen {
+            lines.push(describe_object(id, object, &world.game));
+        }
+    }
+    lines
+}
+
+/// A single line about an object: how hurt a monster is and what it is up
+/// to, what an item does, or what a piece of equipment gives
+fn describe_object(id: usize, object: &Object, game: &Game) -> String {
+    let mut description = match (object.fighter, &object.item, object.equipment) {
+        (Some(fighter), _, _) if id == PLAYER => {
+            format!("You ({}/{} hp)", fighter.hp, object.max_hp(game))
+        }
+        (Some(fighter), _, _) if object.alive => {
+            let doing = match object.ai_state {
+                AiState::Idle => "unaware",
+                AiState::Wander { .. } => "wandering",
+                AiState::Hunt { .. } => "hunting",
+            };
+            format!(
+                "{}, {}, {}",
+                object.name,
+                health_state(fighter.hp, object.max_hp(game)),
+                doing
+            )
+        }
+        (_, Some(_), Some(equipment)) => format!("{}: {}", object.name, equipment.description()),
+        (_, Some(item), None) if object.count > 1 => {
+            format!("{} ({}): {}", object.name, object.count, item.description())
+        }
+        (_, Some(item), None) => format!("{}: {}", object.name, item.description()),
+        _ => object.name.clone(),
+    };
+    if !object.effects.is_empty() {
+        let effects: Vec<_> = object.effects.iter().map(|e| e.kind.adjective()).collect();
+        description += &format!(" ({})", effects.join(", "));
+    }
+    description
+}
+
+/// Roughly how hurt something is, without giving away exact numbers
+fn health_state(hp: i32, max_hp: i32) -> &'static str {
+    let health = hp as f32 / cmp::max(max_hp, 1) as f32;
+    if health >= 1.0 {
+        "unhurt"
+    } else if health > 0.66 {
+        "lightly wounded"
+    } else if health > 0.33 {
+        "wounded"
+    } else if health > 0.1 {
+        "badly wounded"
+    } else {
+        "nearly dead"
+    }
+}
+
+/// Ids of the monsters the player can see, nearest first
+pub fn visible_monsters(world: &World) -> Vec<usize> {
+    let player = &world.objects[PLAYER];
+    let mut monsters: Vec<_> = (0..world.objects.len())
+        .filter(|&id| {
+            let object = &world.objects[id];
+            id != PLAYER
+                && object.alive
+                && object.fighter.is_some()
+                && world.fov.is_in_fov(object.x, object.y)
+                && !object.hidden_from(player)
+        })
+        .collect();
+    monsters.sort_by(|&a, &b| {
+        let (a, b) = (player.distance_to(&world.objects[a]), player.distance_to(&world.objects[b]));
+        a.partial_cmp(&b).unwrap()
+    });
+    monsters
+}
+
+/// The visible monster after the one at `cursor`, nearest first, going
+/// round to the nearest again after the last
+fn next_monster(world: &World, cursor: (i32, i32)) -> Option<usize> {
+    let monsters = visible_monsters(world);
+    let current = monsters
+        .iter()
+        .position(|&id| world.objects[id].pos() == cursor);
+    let next = current.map_or(0, |index| (index + 1) % cmp::max(monsters.len(), 1));
+    monsters.get(next).cloned()
+}
+
+/// Move a cursor over the map by a movement key, keeping it on the map
+fn move_cursor(cursor: (i32, i32), key: Key) -> (i32, i32) {
+    match key_direction(key) {
+        Some((dx, dy)) => (
+            cmp::max(0, cmp::min(MAP_WIDTH - 1, cursor.0 + dx)),
+            cmp::max(0, cmp::min(MAP_HEIGHT - 1, cursor.1 + dy)),
+        ),
+        None => cursor,
+    }
+}
+
+/// Show lines of text in a box over the map, on the side away from `cursor`
+fn draw_description(root: &mut Root, cursor: (i32, i32), lines: &[String]) {
+    let text = lines.join("\n");
+    let height = root.get_height_rect(0, 0, LOOK_WIDTH, MAP_HEIGHT, &text);
+    let mut window = Offscreen::new(LOOK_WIDTH, height);
+    window.set_default_foreground(colors::WHITE);
+    window.print_rect(0, 0, LOOK_WIDTH, height, text);
+    let x = if cursor.0 < MAP_WIDTH / 2 { MAP_WIDTH - LOOK_WIDTH - 1 } else { 1 };
+    tcod::console::blit(&window, (0, 0), (LOOK_WIDTH, height), root, (x, 1), 1.0, 0.7);
+}
+
+/// Look around the map with a cursor moved by the movement keys, with
+/// whatever is under it described. Tab jumps between the visible monsters,
+/// and Escape or `l` leaves.
+fn look_mode(tcod: &mut Tcod, world: &World) {
+    use tcod::input::KeyCode::*;
+
+    let mut cursor = world.objects[PLAYER].pos();
+    loop {
+        render_all(tcod, world);
+        tcod.root.set_char_background(cursor.0, cursor.1, colors::GREY, BackgroundFlag::Set);
+        draw_description(&mut tcod.root, cursor, &describe_tile(world, cursor.0, cursor.1));
+        tcod.root.flush();
+
+        let key = tcod.root.wait_for_keypress(true);
+        match key {
+            Key { code: Escape, .. } | Key { printable: 'l', .. } => break,
+            Key { code: Tab, .. } => {
+                if let Some(id) = next_monster(world, cursor) {
+                    cursor = world.objects[id].pos();
+                }
+            }
+            _ => cursor = move_cursor(cursor, key),
+        }
+    }
+}
+
+pub fn render_all(
+    tcod:               &mut Tcod,
+    world:              &World,
+  ) {
+    let objects = &world.objects;
+    let game = &world.game;
+
+    for y in 0..MAP_HEIGHT {
+        for x in 0..MAP_WIDTH {
+            let visible = world.fov.is_in_fov(x, y);
+            let tile = game.map[x as usize][y as usize];
+            if tile.explored {
+                let terrain = tile.terrain;
+                // what the player sees takes on the color of the light on it
+                let background = if visible {
+                    world.fov.shade(x, y, terrain.background(false), terrain.background(true))
+                } else {
+                    terrain.background(false)
+                };
+                tcod.con.set_char_background(x, y, background, BackgroundFlag::Set);
+                if let Some(glyph) = terrain.glyph() {
+                    // remembered terrain is drawn dimmed
+                    let color = if visible { terrain.color() } else { terrain.color() * 0.5 };
+                    tcod.con.set_default_foreground(color);
+                    tcod.con.put_char(x, y, glyph, BackgroundFlag::None);
+                }
+            }
+        }
+    }
+
+    let mut to_draw: Vec<_> = objects
+        .iter()
+        .filter(|o| {
+            (world.fov.is_in_fov(o.x, o.y) && !o.hidden_from(&objects[PLAYER]) && !o.is_hidden_trap())
+        || (o.always_visible && game.map[o.x as usize][o.y as usize].explored)
+        })
+        .collect();
+    //sort so that non-blocking objects come first
+    to_draw.sort_by(|o1, o2| { o1.blocks.cmp(&o2.blocks) });
+    // draw the objects in the list
+    for object in &to_draw {
+            object.draw(&mut tcod.con);
+        }
+
+    blit(
+        &mut tcod.con, 
+        (0, 0),
+        (MAP_WIDTH, MAP_HEIGHT), 
+        &mut tcod.root, 
+        (0, 0), 
+        1.0,
+        1.0,
+    );
+    // prepare to render GUI panel
+    tcod.panel.set_default_background(colors::BLACK);
+    tcod.panel.clear();
+
+    // print the game messages, one line at a time
+    let mut y = MSG_HEIGHT as i32;
+    for message in game.log.iter().rev() {
+        let msg = message.display();
+        let msg_height = tcod.panel.get_height_rect(MSG_X, y, MSG_WIDTH, 0, &msg);
+        y -= msg_height;
+        if y < 0 {
+            break;
+        }
+        tcod.panel.set_default_foreground(message.color);
+        tcod.panel.print_rect(MSG_X, y, MSG_WIDTH, 0, msg);
+    }
+
+    // show the player's status
+    let hp = objects[PLAYER].fighter.map_or(0, |f| f.hp);
+    let max_hp = objects[PLAYER].max_hp(game);
+    render_bar(&mut tcod.panel, 
+                1, 
+                1, 
+                BAR_WIDTH, 
+                "HP", 
+                hp, 
+                max_hp, 
+                colors::LIGHT_RED, 
+                colors::DARKER_RED,
+    );
+
+    tcod.panel.print_ex(
+        1,
+        3,
+        BackgroundFlag::None,
+        TextAlignment::Left,
+        format!("Dungeon level: {}", game.dungeon_level),
+    );
+
+    // and whatever is affecting the player, with the turns left
+    let effects: Vec<_> = objects[PLAYER]
+        .effects
+        .iter()
+        .map(|e| format!("{} {}", e.kind.adjective(), e.turns))
+        .collect();
+    tcod.panel.set_default_foreground(colors::LIGHT_VIOLET);
+    tcod.panel.print_rect(1, 4, BAR_WIDTH, PANEL_HEIGHT - 4, effects.join(", "));
+
+    tcod.panel.set_default_foreground(colors::LIGHT_GREY);
+    tcod.panel.print_ex(
+        1,
+        0,
+        BackgroundFlag::None,
+        TextAlignment::Left,
+        get_names_under_mouse(tcod.mouse, objects, &world.fov),
+    );
+
+    blit(
+        &tcod.panel, 
+        (0, 0), 
+        (SCREEN_WIDTH, SCREEN_HEIGHT), 
+        &mut tcod.root, 
+        (0, PANEL_Y), 
+        1.0, 
+        1.0
+    );
+}
+
+/// Ask for a stat to raise if the player has enough experience to level up
+fn level_up(world: &World, tcod: &mut Tcod) -> Option<Stat> {
+    if !world.level_up_pending() {
+        return None;
+    }
+    let fighter = world.objects[PLAYER].fighter.unwrap();
+    let mut choice = None;
+    while choice.is_none() {
+        choice = menu(
+            "Level up! Choose a stat raise: \n",
+            &[
+            format!("Constitution (+20 HP, from {})", fighter.base_max_hp),
+            format!("Strength (+1 atk, from {})", fighter.base_power),
+            format!("Agility (+1 def, from {})", fighter.base_defense),
+            ],
+            LEVEL_SCREEN_WIDTH,
+            &mut tcod.root,
+        );
+    }
+    match choice.unwrap() {
+        0 => Some(Stat::Constitution),
+        1 => Some(Stat::Strength),
+        2 => Some(Stat::Agility),
+        _ => unreachable!(),
+    }
+}
+
+pub fn render_bar(
+    panel: &mut Offscreen,
+    x:              i32,
+    y:              i32,
+    total_width:    i32,
+    name:           &str,
+    value:          i32,
+    maximum:        i32,
+    bar_color:      Color,
+    back_color:     Color,
+    ) {
+    // render a bar (HP, experience, etc.) First calculate the width of the bar
+    let bar_width = (value as f32 / maximum as f32 * total_width as f32) as i32;
+
+    // render the background first
+    panel.set_default_background(back_color);
+    panel.rect(x, y, total_width, 1, false, BackgroundFlag::Screen);
+
+    // now render the bar on top
+    panel.set_default_background(bar_color);
+    if bar_width > 0 {
+        panel.rect(x, y, bar_width, 1, false, BackgroundFlag::Screen);
+    }
+
+    //finally some centered text with values
+    panel.set_default_foreground(colors::WHITE);
+    panel.print_ex(x + total_width / 2, 
+                   y, 
+                   BackgroundFlag::None, 
+                   TextAlignment::Center, 
+                   &format!("{}: {}/{}", name, value, maximum),
+                   );
+}
+
+fn menu<T: AsRef<str>>(header: &str, options: &[T], width: i32, root: &mut Root)
+-> Option<usize> {
+    //body
+    assert!(
+        options.len() <= 26,
+        "Cannot have a menu with more thatn 26 options."
+    );
+
+    let header_height = if header.is_empty() {
+        0
+    } else {
+        root.get_height_rect(0, 0, width, constants::SCREEN_HEIGHT, header)
+    };
+
+    let height = options.len() as i32 + header_height;
+
+    let mut window = Offscreen::new(width, height);
+
+    window.set_default_foreground(colors::WHITE);
+    window.print_rect_ex(
+        0,
+        0,
+        width,
+        height,
+        BackgroundFlag::None,
+        TextAlignment::Left,
+        header,
+    );
+
+    for (index, option_text) in options.iter().enumerate() {
+        let menu_letter = (b'a' + index as u8) as char;
+        let text = format!("({}) {}", menu_letter, option_text.as_ref());
+        window.print_ex(
+            0,
+            header_height + index as i32,
+            BackgroundFlag::None,
+            TextAlignment::Left,
+            text,
+        );
+    }
+    let x = constants::SCREEN_WIDTH / 2 - width /2;
+    let y = constants::SCREEN_HEIGHT / 2 - height /2;
+    tcod::console::blit(&mut window, (0, 0), (width, height), root, (x, y), 1.0, 0.7);
+    
+    root.flush();
+    let key = root.wait_for_keypress(true);
+
+    if key.printable.is_alphabetic() {
+        let index = key.printable.to_ascii_lowercase() as usize - 'a' as usize;
+        if index < options.len() {
+            Some(index)
+        } else {
+            None
+        }
+    } else {
+        None
+    }
+}
+
+fn msgbox(text: &str, width: i32, root: &mut Root) {
+    let options: &[&str] = &[];
+    menu(text, options, width, root);
+}
+
+/// Show every message kept, newest at the bottom, with the turn each was
+/// said on and its category. The arrow keys, Page Up/Down, Home and End
+/// scroll, Tab cycles between all messages and a single category, and
+/// Escape closes it.
+fn message_history(tcod: &mut Tcod, world: &World) {
+    use tcod::input::KeyCode::*;
+
+    let filters = [
+        None,
+        Some(MessageCategory::Combat),
+        Some(MessageCategory::Items),
+        Some(MessageCategory::System),
+    ];
+    let mut filter = 0;
+    // how many of the newest messages are scrolled past
+    let mut scroll = 0;
+    let page = (SCREEN_HEIGHT - 2) as usize;
+    loop {
+        let messages: Vec<_> = world
+            .game
+            .log
+            .iter()
+            .filter(|message| filters[filter].is_none_or(|category| message.category == category))
+            .collect();
+        scroll = cmp::min(scroll, messages.len().saturating_sub(1));
+
+        tcod.root.set_default_background(colors::BLACK);
+        tcod.root.clear();
+        tcod.root.set_default_foreground(colors::WHITE);
+        tcod.root.print(
+            0,
+            0,
+            format!(
+                "Messages: {} ({})   Tab: filter   Arrows, PgUp/PgDn: scroll   Esc: close",
+                filters[filter].map_or("all", |category| category.name()),
+                messages.len()
+            ),
+        );
+        // fill the screen from the bottom up
+        let mut y = SCREEN_HEIGHT;
+        // each message is marked with its turn and, in its color, its category
+        let text_x = 16;
+        for message in messages.iter().rev().skip(scroll) {
+            let text = message.display();
+            y -= tcod.root.get_height_rect(text_x, 0, SCREEN_WIDTH - text_x, 0, &text);
+            if y < 2 {
+                break;
+            }
+            tcod.root.set_default_foreground(message.category.color());
+            tcod.root.print(0, y, format!("[{:>5}] {}", message.turn, message.category.name()));
+            tcod.root.set_default_foreground(message.color);
+            tcod.root.print_rect(text_x, y, SCREEN_WIDTH - text_x, 0, text);
+        }
+        tcod.root.flush();
+
+        let key = tcod.root.wait_for_keypress(true);
+        match key.code {
+            Escape => break,
+            Tab => {
+                filter = (filter + 1) % filters.len();
+                scroll = 0;
+            }
+            Up | NumPad8 => scroll += 1,
+            Down | NumPad2 => scroll = scroll.saturating_sub(1),
+            PageUp | NumPad9 => scroll += page,
+            PageDown | NumPad3 => scroll = scroll.saturating_sub(page),
+            Home | NumPad7 => scroll = messages.len(),
+            End | NumPad1 => scroll = 0,
+            _ => {}
+        }
+    }
+}
+
+fn inventory_menu(inventory: &[Object], header: &str, root: &mut Root)
+-> Option<usize> {
+    let options = if inventory.len() == 0 {
+        vec!["Inventory is empty.".into()]
+    } else {
+        inventory
+            .iter()
+            .map(|item| { 
+            match item.equipment {
+                Some(equipment) if equipment.equipped => {
+                    format!("{} (on {})", item.name, equipment.slot)
+                }
+                _ if item.count > 1 => format!("{} ({})", item.name, item.count),
+                _ => item.name.clone(),
+            }
+        })
+        .collect()
+    };
+        let inventory_index = menu(header, &options, constants::INVENTORY_WIDTH, root);
+        
+        if inventory.len() > 0 {
+            inventory_index
+        } else {
+            None
+        }
+        
+}
+
+/// The direction a movement key stands for
+fn key_direction(key: Key) -> Option<(i32, i32)> {
+    use tcod::input::KeyCode::*;
+    match key.code {
+        Up | NumPad8 => Some((0, -1)),
+        Down | NumPad2 => Some((0, 1)),
+        Left | NumPad4 => Some((-1, 0)),
+        Right | NumPad6 => Some((1, 0)),
+        Home | NumPad7 => Some((-1, -1)),
+        PageUp | NumPad9 => Some((1, -1)),
+        End | NumPad1 => Some((-1, 1)),
+        PageDown | NumPad3 => Some((1, 1)),
+        _ => None,
+    }
+}
+
+/// Ask for a direction with the movement keys, or None if cancelled
+fn choose_direction(tcod: &mut Tcod, world: &mut World, prompt: &str) -> Option<(i32, i32)> {
+    use tcod::input::KeyCode::Escape;
+
+    world.game.log.add(prompt, colors::LIGHT_CYAN, MessageCategory::System);
+    render_all(tcod, world);
+    tcod.root.flush();
+    loop {
+        let key = tcod.root.wait_for_keypress(true);
+        if key.code == Escape {
+            return None;
+        }
+        if let Some(direction) = key_direction(key) {
+            return Some(direction);
+        }
+    }
+}
+
+/// Ask the player for whatever target the item needs, if any
+fn choose_target(inventory_id: usize, tcod: &mut Tcod, world: &mut World) -> Option<(i32, i32)> {
+    let targeting = world.game.inventory[inventory_id].item.as_ref()?.targeting()?;
+    let prompt = match targeting {
+        Targeting::Monster { .. } => "Pick an enemy to strike: move the cursor, Tab for the next \
+                                      enemy, Enter to confirm, Escape to cancel.",
+        Targeting::Tile { .. } => "Pick where it lands: move the cursor, Tab for the next \
+                                   enemy, Enter to confirm, Escape to cancel.",
+    };
+    world.game.log.add(prompt, colors::LIGHT_CYAN, MessageCategory::System);
+    target_tile(tcod, world, targeting)
+}
+
+/// Whether the tile can be picked as a target
+fn valid_target(world: &World, targeting: Targeting, (x, y): (i32, i32)) -> bool {
+    let player = &world.objects[PLAYER];
+    let in_range = |max_range: Option<f32>| {
+        max_range.is_none_or(|range| player.distance(x, y) <= range)
+    };
+    if !world.fov.is_in_fov(x, y) {
+        return false;
+    }
+    match targeting {
+        Targeting::Monster { max_range } => {
+            in_range(max_range)
+                && visible_monsters(world)
+                    .iter()
+                    .any(|&id| world.objects[id].pos() == (x, y))
+        }
+        Targeting::Tile { max_range, .. } => in_range(max_range),
+    }
+}
+
+/// Mark up the map for a target at `cursor`: visible tiles out of range are
+/// darkened, the line a projectile would take towards the cursor is drawn,
+/// along with everything a blast would catch, and the cursor shows whether
+/// the target can be picked
+fn draw_targeting(root: &mut Root, world: &World, targeting: Targeting, cursor: (i32, i32)) {
+    let player = &world.objects[PLAYER];
+    let (max_range, radius) = match targeting {
+        Targeting::Monster { max_range } => (max_range, 0),
+        Targeting::Tile { max_range, radius } => (max_range, radius),
+    };
+    let in_range = |x: i32, y: i32| max_range.is_none_or(|range| player.distance(x, y) <= range);
+
+    for y in 0..MAP_HEIGHT {
+        for x in 0..MAP_WIDTH {
+            if !world.fov.is_in_fov(x, y) {
+                continue;
+            }
+            let background = root.get_char_background(x, y);
+            if !in_range(x, y) {
+                root.set_char_background(x, y, background * 0.5, BackgroundFlag::Set);
+            } else if radius > 0
+                && (x - cursor.0).pow(2) + (y - cursor.1).pow(2) <= radius.pow(2)
+            {
+                root.set_char_background(x, y, lerp(background, ORANGE, 0.5), BackgroundFlag::Set);
+            }
+        }
+    }
+
+    // the way there, as far as a projectile would get
+    let from = player.pos();
+    let flight = fly(from, cursor, line_steps(from, cursor), &world.game.map, &world.objects);
+    for &(x, y) in flight.path.iter().filter(|&&pos| pos != cursor) {
+        let background = root.get_char_background(x, y);
+        let color = if in_range(x, y) { LIGHT_YELLOW } else { RED };
+        root.set_char_background(x, y, lerp(background, color, 0.4), BackgroundFlag::Set);
+    }
+
+    let color = if valid_target(world, targeting, cursor) { GREY } else { DARK_RED };
+    root.set_char_background(cursor.0, cursor.1, color, BackgroundFlag::Set);
+}
+
+/// Pick a target with a cursor, moved by the movement keys or the mouse,
+/// starting on the nearest enemy. Tab jumps between the visible enemies,
+/// Enter or a left click confirm and Escape or a right click cancel.
+fn target_tile(tcod: &mut Tcod, world: &World, targeting: Targeting) -> Option<(i32, i32)> {
+    use tcod::input::KeyCode::*;
+
+    let player = &world.objects[PLAYER];
+    let mut cursor = next_monster(world, player.pos())
+        .map_or(player.pos(), |id| world.objects[id].pos());
+    let max_range = match targeting {
+        Targeting::Monster { max_range } | Targeting::Tile { max_range, .. } => max_range,
+    };
+    loop {
+        render_all(tcod, world);
+        draw_targeting(&mut tcod.root, world, targeting, cursor);
+        let mut lines = describe_tile(world, cursor.0, cursor.1);
+        let distance = player.distance(cursor.0, cursor.1);
+        lines.push(match max_range {
+            Some(range) => format!("Distance {:.0} of {:.0}", distance, range),
+            None => format!("Distance {:.0}", distance),
+        });
+        draw_description(&mut tcod.root, cursor, &lines);
+        tcod.root.flush();
+
+        let event = input::check_for_event(input::KEY_PRESS | input::MOUSE).map(|e| e.1);
+        match event {
+            Some(Event::Mouse(mouse)) => {
+                let moved = (mouse.cx, mouse.cy) != (tcod.mouse.cx, tcod.mouse.cy);
+                tcod.mouse = mouse;
+                let (x, y) = (mouse.cx as i32, mouse.cy as i32);
+                if x < MAP_WIDTH && y < MAP_HEIGHT && (moved || mouse.lbutton_pressed) {
+                    cursor = (x, y);
+                }
+                if mouse.lbutton_pressed && valid_target(world, targeting, cursor) {
+                    return Some(cursor);
+                }
+                if mouse.rbutton_pressed {
+                    return None;
+                }
+            }
+            Some(Event::Key(key)) => match key.code {
+                Escape => return None,
+                Enter | NumPadEnter if valid_target(world, targeting, cursor) => {
+                    return Some(cursor);
+                }
+                Tab => {
+                    if let Some(id) = next_monster(world, cursor) {
+                        cursor = world.objects[id].pos();
+                    }
+                }
+                _ => cursor = move_cursor(cursor, key),
+            },
+            None => {}
+        }
+    }
+}
@@ -0,0 +1,996 @@
+use std::io::{ Read, Write };
+use std::fs::File;
+use std::collections::BTreeMap;
+use std::error::Error;
+
+use tcod::colors;
+
+use super::*;
+
+/// A command issued by the player, independent of how it was entered.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Command {
+    /// move by the given offset, attacking anything standing there
+    Move(i32, i32),
+    Wait,
+    PickUp,
+    UseItem {
+        inventory_id: usize,
+        target: Option<(i32, i32)>,
+    },
+    Drop(usize),
+    TakeStairs,
+    /// open or close the door in the given direction
+    Door(i32, i32),
+    /// look around for hidden traps
+    Search,
+    /// shoot the equipped launcher towards a tile
+    Fire { target: (i32, i32) },
+    /// throw one of an inventory item towards a tile
+    Throw {
+        inventory_id: usize,
+        target: (i32, i32),
+    },
+    LevelUp(Stat),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Stat {
+    Constitution,
+    Strength,
+    Agility,
+}
+
+/// Something that happened in the world while carrying out a command.
+#[derive(Clone, Debug, PartialEq)]
+pub enum GameEvent {
+    /// the object ids are `Object::id`, not places in the list of objects
+    Attacked {
+        attacker: u64,
+        target: u64,
+        damage: i32,
+        critical: bool,
+    },
+    Missed {
+        attacker: u64,
+        target: u64,
+    },
+    Died { id: u64 },
+    PickedUp { name: String },
+    Dropped { name: String },
+    UsedItem { name: String },
+    Descended { dungeon_level: u32 },
+    Ascended { dungeon_level: u32 },
+    LeveledUp { level: i32 },
+    /// something flew across the map along `path`, drawn as `glyph`
+    Projectile {
+        path: Vec<(i32, i32)>,
+        glyph: char,
+        color: Color,
+    },
+}
+
+/// The complete game simulation. It owns everything needed to play, but
+/// knows nothing about windows or input devices, so it runs headless.
+pub struct World {
+    pub objects: Vec<Object>,
+    pub game: Game,
+    /// what the player can see, and how the level is lit
+    pub fov: Vision,
+    pub catalog: Catalog,
+}
+
+impl World {
+    /// Start a new game with a fresh player on the first level. The same
+    /// seed always generates the same dungeon.
+    pub fn new(seed: u64, catalog: Catalog) -> Self {
+        let mut player = Object::new(0, 0, '@', colors::WHITE, "player", true);
+        player.alive = true;
+        player.fighter = Some(Fighter {
+            base_max_hp:     100,
+            hp:         100,
+            base_defense:    1,
+            base_power:      2,
+            on_death:   DeathCallback::Player,
+            xp:         0,
+            base_speed: NORMAL_SPEED,
+            energy:     ACTION_COST,
+            sight:      TORCH_RADIUS,
+            damage:     Dice { count: 1, sides: 3, bonus: 0 },
+            accuracy:   0,
+            evasion:    0,
+        });
+
+        let mut objects = vec![player];
+        let level = 1;
+        let mut rng = GameRng::new(seed);
+
+        let mut game = Game {
+            map: make_map(&mut objects, level, &mut rng, &catalog),
+            log: Messages::new(),
+            inventory: vec![],
+            dungeon_level: 1,
+            turn: 1,
+            levels: BTreeMap::new(),
+            seed,
+            rng,
+            commands_issued: 0,
+            last_id: 0,
+            events: vec![],
+            map_changed: false,
+        };
+
+        // the starting kit, with every piece of equipment that fits worn
+        for item in catalog.items.iter().filter(|item| item.starting) {
+            let mut object = item.spawn(0, 0);
+            if let Some(ref mut equipment) = object.equipment {
+                equipment.equipped = get_equipped_in_slot(equipment.slot, &game.inventory).is_none();
+            }
+            game.inventory.push(object);
+        }
+
+        game.log.set_turn(game.turn);
+        game.log.add(
+            "Welcome stranger! Prepare to perish.",
+            colors::RED,
+            MessageCategory::System,
+        );
+
+        World::from_parts(objects, game, catalog)
+    }
+
+    /// Build a world around existing objects and game state, e.g. a loaded save
+    pub fn from_parts(objects: Vec<Object>, game: Game, catalog: Catalog) -> Self {
+        let mut world = World {
+            objects,
+            game,
+            fov: Vision::new(MAP_WIDTH, MAP_HEIGHT),
+            catalog,
+        };
+        world.claim_ids();
+        world.initialize_fov();
+        world
+    }
+
+    /// Hand out ids to objects new to the world, like a freshly made level
+    /// or anything saved before objects had ids
+    fn claim_ids(&mut self) {
+        let Game { levels, inventory, last_id, .. } = &mut self.game;
+        let stored = levels.values_mut().flat_map(|level| level.objects.iter_mut());
+        let mut all: Vec<&mut Object> =
+            self.objects.iter_mut().chain(inventory.iter_mut()).chain(stored).collect();
+        *last_id = all.iter().map(|object| object.id).fold(*last_id, cmp::max);
+        for object in all.iter_mut().filter(|object| object.id == 0) {
+            *last_id += 1;
+            object.id = *last_id;
+        }
+    }
+
+    /// Carry out one player command, and let the monsters act if it took a
+    /// turn. Returns everything that happened along the way.
+    pub fn step(&mut self, command: Command) -> Vec<GameEvent> {
+        self.game.commands_issued += 1;
+        // anything put in the world since the last step
+        self.claim_ids();
+        let from = self.objects[PLAYER].pos();
+        let player_action = self.player_command(command);
+        if player_action == PlayerAction::TookTurn {
+            let cost = self.action_cost(PLAYER, from);
+            self.objects[PLAYER].fighter.as_mut().unwrap().energy -= cost;
+            self.arrive(PLAYER, from);
+            // traps right next to the player may catch their eye
+            find_traps(1.5, PERCEPTION_PERCENT, &mut self.objects, &mut self.game);
+        }
+        if self.objects[PLAYER].alive && player_action == PlayerAction::TookTurn {
+            self.run_until_player_ready();
+            clear_spent_lights(&mut self.objects);
+        }
+        // what the player sees is worked out once everyone has acted
+        self.update_fov();
+        if player_action == PlayerAction::TookTurn {
+            self.game.turn += 1;
+            self.game.log.set_turn(self.game.turn);
+        }
+        self.game.events.drain(..).collect()
+    }
+
+    /// Hand out energy by speed, one tick at a time, letting each monster act
+    /// whenever it has saved up enough, until the player can act again
+    fn run_until_player_ready(&mut self) {
+        let can_act = |object: &Object| object.fighter.is_some_and(|f| f.energy >= ACTION_COST);
+
+        while self.objects[PLAYER].alive && !can_act(&self.objects[PLAYER]) {
+            for object in self.objects.iter_mut() {
+                let speed = object.speed();
+                if let Some(fighter) = object.fighter.as_mut() {
+                    fighter.energy += speed;
+                }
+            }
+
+            let mut fallen = vec![];
+            for id in 0..self.objects.len() {
+                while self.objects[PLAYER].alive
+                    && self.objects[id].ai.is_some()
+                    && can_act(&self.objects[id])
+                {
+                    let from = self.objects[id].pos();
+                    ai_take_turn(id, &mut self.game, &mut self.objects);
+                    let cost = self.action_cost(id, from);
+                    if let Some(fighter) = self.objects[id].fighter.as_mut() {
+                        fighter.energy -= cost;
+                    }
+                    if self.arrive(id, from) {
+                        // gone from the level, so its turns are over for good
+                        let monster = &mut self.objects[id];
+                        monster.ai = None;
+                        monster.blocks = false;
+                        fallen.push(id);
+                    }
+                }
+            }
+            // monsters that fell into a chasm or pit are gone from this level
+            fallen.sort();
+            fallen.dedup();
+            for &id in fallen.iter().rev() {
+                self.objects.remove(id);
+            }
+
+            // effects wear off after everyone had their chance to act under them
+            tick_status_effects(&mut self.objects, &mut self.game);
+            burn_down_lights(&mut self.objects);
+        }
+    }
+
+    /// Energy spent on an action: stepping onto slow terrain costs extra
+    fn action_cost(&self, id: usize, from: (i32, i32)) -> i32 {
+        let (x, y) = self.objects[id].pos();
+        if (x, y) == from {
+            ACTION_COST
+        } else {
+            ACTION_COST * self.game.map[x as usize][y as usize].terrain.move_cost() / 100
+        }
+    }
+
+    /// Whatever the terrain and any trap do to someone who just stepped onto
+    /// a tile. Returns whether a monster fell out of the level.
+    fn arrive(&mut self, id: usize, from: (i32, i32)) -> bool {
+        let level = self.game.dungeon_level;
+        let fell = self.enter_terrain(id, from);
+        // a player who fell has already left the level
+        if fell || self.game.dungeon_level != level {
+            return fell;
+        }
+        self.spring_trap(id, from)
+    }
+
+    /// Set off any trap where someone just stepped. Returns whether a monster
+    /// fell out of the level.
+    fn spring_trap(&mut self, id: usize, from: (i32, i32)) -> bool {
+        let (x, y) = self.objects[id].pos();
+        if (x, y) == from || !self.objects[id].alive {
+            return false;
+        }
+        let trap_id = self
+            .objects
+            .iter()
+            .position(|object| object.pos() == (x, y) && object.trap.is_some());
+        let trap_id = match trap_id {
+            Some(trap_id) => trap_id,
+            None => return false,
+        };
+        let kind = self.objects[trap_id].trap.unwrap().kind;
+        // only what the player sees happen gets told about
+        let seen = id == PLAYER || self.fov.is_in_fov(x, y);
+        if seen {
+            reveal_trap(&mut self.objects[trap_id]);
+            let verb = if id == PLAYER { "set" } else { "sets" };
+            self.game.log.add_entry(
+                Message::new(
+                    format!("{} {} off a {}!", self.objects[id].subject(), verb, kind.name()),
+                    colors::LIGHT_RED,
+                    MessageCategory::Combat,
+                )
+                .by(self.objects[id].id),
+            );
+        }
+
+        match kind {
+            TrapKind::Dart => {
+                self.objects[id].add_status(StatusEffect {
+                    kind: StatusKind::Poison,
+                    turns: DART_POISON_TURNS,
+                    potency: 1,
+                });
+                let died = self.objects[id].take_damage(DART_DAMAGE, &mut self.game).is_some();
+                if died {
+                    self.game.events.push(GameEvent::Died { id: self.objects[id].id });
+                }
+            }
+            TrapKind::Teleport => {
+                teleport(id, &mut self.objects, &mut self.game);
+            }
+            TrapKind::Alarm => {
+                self.game.log.add(
+                    "A loud alarm rings out!",
+                    colors::LIGHT_RED,
+                    MessageCategory::Combat,
+                );
+                for object in self.objects.iter_mut().filter(|object| object.ai.is_some()) {
+                    object.ai_state = AiState::Hunt { x, y };
+                }
+            }
+            TrapKind::Pit if id == PLAYER => self.fall("The floor gives way under you!"),
+            TrapKind::Pit => {
+                if seen {
+                    self.game.log.add_entry(
+                        Message::new(
+                            format!("{} falls into the pit!", self.objects[id].name),
+                            colors::LIGHT_GREY,
+                            MessageCategory::Combat,
+                        )
+                        .by(self.objects[id].id),
+                    );
+                }
+                return true;
+            }
+            TrapKind::Gas => {
+                if seen {
+                    self.game.log.add(
+                        "A cloud of gas billows out!",
+                        colors::LIGHT_GREEN,
+                        MessageCategory::Combat,
+                    );
+                }
+                let trap = &self.objects[trap_id];
+                let caught: Vec<_> = (0..self.objects.len())
+                    .filter(|&other| {
+                        self.objects[other].fighter.is_some()
+                            && self.objects[other].distance_to(trap) <= GAS_RADIUS
+                    })
+                    .collect();
+                for other in caught {
+                    self.objects[other].add_status(StatusEffect {
+                        kind: StatusKind::Confusion,
+                        turns: GAS_CONFUSE_TURNS,
+                        potency: 0,
+                    });
+                }
+            }
+        }
+        false
+    }
+
+    /// Whatever the terrain does to someone who just stepped onto it.
+    /// Returns whether a monster fell out of the level.
+    fn enter_terrain(&mut self, id: usize, from: (i32, i32)) -> bool {
+        let (x, y) = self.objects[id].pos();
+        if (x, y) == from || !self.objects[id].alive {
+            return false;
+        }
+        let (from_x, from_y) = from;
+        let previous = self.game.map[from_x as usize][from_y as usize].terrain;
+        match self.game.map[x as usize][y as usize].terrain {
+            Terrain::Lava => {
+                let verb = if id == PLAYER { "burn" } else { "burns" };
+                self.game.log.add_entry(
+                    Message::new(
+                        format!("{} {} in the lava!", self.objects[id].subject(), verb),
+                        colors::ORANGE,
+                        MessageCategory::Combat,
+                    )
+                    .by(self.objects[id].id),
+                );
+                let died = self.objects[id].take_damage(LAVA_DAMAGE, &mut self.game).is_some();
+                if died {
+                    self.game.events.push(GameEvent::Died { id: self.objects[id].id });
+                }
+                false
+            }
+            Terrain::Chasm if id == PLAYER => {
+                self.fall("You fall into the chasm!");
+                false
+            }
+            Terrain::Chasm => {
+                self.game.log.add_entry(
+                    Message::new(
+                        format!("{} falls into the chasm!", self.objects[id].name),
+                        colors::LIGHT_GREY,
+                        MessageCategory::Combat,
+                    )
+                    .by(self.objects[id].id),
+                );
+                true
+            }
+            Terrain::DeepWater if id == PLAYER && previous != Terrain::DeepWater => {
+                self.game.log.add(
+                    "You swim into deep water.",
+                    colors::LIGHT_BLUE,
+                    MessageCategory::System,
+                );
+                false
+            }
+            _ => false,
+        }
+    }
+
+    /// Drop through to the level below, landing by its stairs up
+    fn fall(&mut self, message: &str) {
+        self.game.log.add(message, colors::RED, MessageCategory::Combat);
+        let died = self.objects[PLAYER]
+            .take_damage(CHASM_FALL_DAMAGE, &mut self.game)
+            .is_some();
+        if died {
+            self.game.events.push(GameEvent::Died { id: self.objects[PLAYER].id });
+            return;
+        }
+        let level = self.game.dungeon_level + 1;
+        self.enter_level(level, STAIRS_UP);
+        self.game.events.push(GameEvent::Descended { dungeon_level: level });
+    }
+
+    /// Experience the player needs to reach the next level
+    pub fn level_up_xp(&self) -> i32 {
+        LEVEL_UP_BASE + self.objects[PLAYER].level * LEVEL_UP_FACTOR
+    }
+
+    /// Whether the player has enough experience to level up. Front-ends
+    /// should answer it with a `Command::LevelUp` before anything else.
+    pub fn level_up_pending(&self) -> bool {
+        self.objects[PLAYER].fighter.map_or(0, |f| f.xp) >= self.level_up_xp()
+    }
+
+    fn player_command(&mut self, command: Command) -> PlayerAction {
+        use Command::*;
+        use PlayerAction::*;
+
+        if !self.objects[PLAYER].alive {
+            return DidntTakeTurn;
+        }
+
+        // a stunned player can't do anything that would take a turn, so the
+        // turn just passes
+        let command = match command {
+            Move(..) | Wait | Fire { .. } | Throw { .. }
+                if self.objects[PLAYER].has_status(StatusKind::Stun) =>
+            {
+                self.game.log.add_entry(
+                    Message::new(
+                        "You are stunned and cannot act!",
+                        colors::LIGHT_GREY,
+                        MessageCategory::Combat,
+                    )
+                    .by(self.objects[PLAYER].id),
+                );
+                Wait
+            }
+            _ => command,
+        };
+
+        match command {
+            Move(dx, dy) => {
+                player_move_or_attack(dx, dy, &mut self.objects, &mut self.game);
+                TookTurn
+            }
+            Wait => TookTurn,
+            PickUp => {
+                let player_pos = self.objects[PLAYER].pos();
+                let item_id = self
+                    .objects
+                    .iter()
+                    .position(|object| object.pos() == player_pos && object.item.is_some());
+                if let Some(item_id) = item_id {
+                    pick_item_up(item_id, &mut self.objects, &mut self.game);
+                }
+                DidntTakeTurn
+            }
+            UseItem { inventory_id, target } => {
+                if inventory_id < self.game.inventory.len() {
+                    use_item(inventory_id, target, &mut self.objects, &mut self.game, &self.fov);
+                }
+                DidntTakeTurn
+            }
+            Drop(inventory_id) => {
+                if inventory_id < self.game.inventory.len() {
+                    drop_item(inventory_id, &mut self.game, &mut self.objects);
+                }
+                DidntTakeTurn
+            }
+            TakeStairs => {
+                let player_pos = self.objects[PLAYER].pos();
+                let on_stairs = |name: &str| {
+                    self.objects
+                        .iter()
+                        .any(|object| object.pos() == player_pos && object.name == name)
+                };
+                let (down, up) = (on_stairs(STAIRS_DOWN), on_stairs(STAIRS_UP));
+                if down {
+                    self.next_level();
+                } else if up {
+                    self.previous_level();
+                }
+                DidntTakeTurn
+            }
+            Door(dx, dy) => {
+                let (x, y) = self.objects[PLAYER].pos();
+                let (x, y) = (x + dx, y + dy);
+                // only a door right next to the player can be reached
+                let adjacent = dx.abs() <= 1 && dy.abs() <= 1;
+                let on_map = x >= 0 && y >= 0 && x < MAP_WIDTH && y < MAP_HEIGHT;
+                let terrain = if adjacent && on_map {
+                    self.game.map[x as usize][y as usize].terrain
+                } else {
+                    Terrain::Wall
+                };
+                match terrain {
+                    Terrain::OpenDoor if close_door(x, y, &self.objects, &mut self.game) => TookTurn,
+                    Terrain::ClosedDoor | Terrain::LockedDoor
+                        if player_open_door(x, y, &mut self.game) =>
+                    {
+                        TookTurn
+                    }
+                    Terrain::OpenDoor | Terrain::ClosedDoor | Terrain::LockedDoor => DidntTakeTurn,
+                    _ => {
+                        self.game.log.add(
+                            "There is no door there.",
+                            colors::LIGHT_GREY,
+                            MessageCategory::System,
+                        );
+                        DidntTakeTurn
+                    }
+                }
+            }
+            Search => {
+                let found = find_traps(SEARCH_RADIUS, SEARCH_PERCENT, &mut self.objects, &mut self.game);
+                if found == 0 {
+                    self.game.log.add(
+                        "You search around, but find nothing.",
+                        colors::LIGHT_GREY,
+                        MessageCategory::System,
+                    );
+                }
+                TookTurn
+            }
+            Fire { target } => {
+                if player_fire(target, &mut self.objects, &mut self.game) {
+                    TookTurn
+                } else {
+                    DidntTakeTurn
+                }
+            }
+            Throw { inventory_id, target } => {
+                if inventory_id < self.game.inventory.len() {
+                    throw_item(inventory_id, target, &mut self.objects, &mut self.game);
+                    TookTurn
+                } else {
+                    DidntTakeTurn
+                }
+            }
+            LevelUp(stat) => {
+                self.level_up(stat);
+                DidntTakeTurn
+            }
+        }
+    }
+
+    fn level_up(&mut self, stat: Stat) {
+        if !self.level_up_pending() {
+            return;
+        }
+        let level_up_xp = self.level_up_xp();
+        let player = &mut self.objects[PLAYER];
+        player.level += 1;
+        self.game.log.add(
+            format!(
+                "Your battle skills grow stronger! You reached level {}!",
+                player.level
+            ),
+            colors::YELLOW,
+            MessageCategory::System,
+        );
+        self.game.events.push(GameEvent::LeveledUp { level: player.level });
+
+        let fighter = player.fighter.as_mut().unwrap();
+        fighter.xp -= level_up_xp;
+        match stat {
+            Stat::Constitution => {
+                fighter.base_max_hp += 20;
+                fighter.hp += 20;
+            }
+            Stat::Strength => {
+                fighter.base_power +=1;
+            }
+            Stat::Agility => {
+                fighter.base_defense += 1;
+            }
+        }
+    }
+
+    /// Go down the stairs, to a fresh level if this is the first time
+    fn next_level(&mut self) {
+        let level = self.game.dungeon_level + 1;
+        if self.enter_level(level, STAIRS_UP) {
+            self.game.log.add("You descend the stairs.", colors::RED, MessageCategory::System);
+        } else {
+            self.game.log.add(
+                "You take a moment to rest, and recover your strength.",
+                colors::VIOLET,
+                MessageCategory::System,
+            );
+            let heal_hp = self.objects[PLAYER].max_hp(&self.game) / 2;
+            self.objects[PLAYER].heal(heal_hp, &self.game);
+
+            self.game.log.add(
+                "After a rare moment of peace, you descend deeper into \
+                the heart of the dungeon...",
+                colors::RED,
+                MessageCategory::System,
+            );
+        }
+        self.game.events.push(GameEvent::Descended { dungeon_level: level });
+    }
+
+    /// Go back up the stairs, to the level above as it was left
+    fn previous_level(&mut self) {
+        let level = self.game.dungeon_level - 1;
+        self.enter_level(level, STAIRS_DOWN);
+        self.game.log.add("You climb back up the stairs.", colors::VIOLET, MessageCategory::System);
+        self.game.events.push(GameEvent::Ascended { dungeon_level: level });
+    }
+
+    /// Put the current level away and move the player to another one, onto
+    /// the stairs named `arrive_on` if it was visited before. Returns
+    /// whether it had been.
+    fn enter_level(&mut self, level: u32, arrive_on: &str) -> bool {
+        let map = std::mem::take(&mut self.game.map);
+        let objects = self.objects.split_off(PLAYER + 1);
+        self.game.levels.insert(self.game.dungeon_level, Level { map, objects });
+        self.game.dungeon_level = level;
+
+        let visited = match self.game.levels.remove(&level) {
+            Some(stored) => {
+                self.game.map = stored.map;
+                self.objects.extend(stored.objects);
+                let stairs = self.objects.iter().position(|object| object.name == arrive_on);
+                if let Some(stairs) = stairs {
+                    let (x, y) = self.objects[stairs].pos();
+                    self.objects[PLAYER].set_pos(x, y);
+                }
+                true
+            }
+            None => {
+                self.game.map = make_map(&mut self.objects, level, &mut self.game.rng, &self.catalog);
+                false
+            }
+        };
+        self.claim_ids();
+        self.game.map_changed = true;
+        visited
+    }
+
+    /// Copy what blocks sight and movement from the map into the FOV map,
+    /// and recompute what the player sees
+    fn initialize_fov(&mut self) {
+        for y in 0..MAP_HEIGHT {
+            for x in 0..MAP_WIDTH {
+                self.fov.set(
+                    x,
+                    y,
+                    !self.game.map[x as usize][y as usize].block_sight(),
+                    !self.game.map[x as usize][y as usize].blocked(),
+                );
+            }
+        }
+        self.compute_fov();
+    }
+
+    /// Recompute what the player sees, going over the whole map again only
+    /// if it changed
+    fn update_fov(&mut self) {
+        if self.game.map_changed {
+            self.game.map_changed = false;
+            self.initialize_fov();
+        } else {
+            self.compute_fov();
+        }
+    }
+
+    /// Recompute how the level is lit and what the player sees, exploring
+    /// every visible tile
+    fn compute_fov(&mut self) {
+        self.fov.compute(&self.objects, &self.game.inventory);
+
+        for y in 0..MAP_HEIGHT {
+            for x in 0..MAP_WIDTH {
+                if self.fov.is_in_fov(x, y) {
+                    self.game.map[x as usize][y as usize].explored = true;
+                }
+            }
+        }
+    }
+}
+
+pub fn save_game(world: &World) -> Result<(), Box<dyn Error>> {
+    save_game_to("savegame", world)
+}
+
+/// Like `save_game`, with the save kept at `path`
+pub fn save_game_to(path: &str, world: &World) -> Result<(), Box<dyn Error>> {
+    let save_data = serde_json::to_string(&(&world.objects, &world.game))?;
+    let mut file = File::create(path)?;
+    file.write_all(save_data.as_bytes())?;
+    Ok(())
+}
+
+pub fn load_game(catalog: Catalog) -> Result<World, Box<dyn Error>> {
+    load_game_from("savegame", catalog)
+}
+
+/// Like `load_game`, with the save kept at `path`
+pub fn load_game_from(path: &str, catalog: Catalog) -> Result<World, Box<dyn Error>> {
+    let mut json_save_state = String::new();
+    let mut file = File::open(path)?;
+    file.read_to_string(&mut json_save_state)?;
+    let (objects, game) = serde_json::from_str::<(Vec<Object>, Game)>(&json_save_state)?;
+    Ok(World::from_parts(objects, game, catalog))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A world with just the player, in an open room
+    fn arena() -> World {
+        let mut world = World::new(1, Catalog::load().unwrap());
+        world.objects.truncate(PLAYER + 1);
+        world.game.map = vec![vec![Tile::wall(); MAP_HEIGHT as usize]; MAP_WIDTH as usize];
+        create_room(Rect::new(0, 0, 20, 10), &mut world.game.map);
+        world.objects[PLAYER].set_pos(5, 5);
+        world.initialize_fov();
+        world
+    }
+
+    fn spawn(world: &mut World, name: &str, x: i32, y: i32) {
+        let def = world.catalog.monsters.iter().find(|m| m.name == name).unwrap();
+        let monster = def.spawn(x, y);
+        world.objects.push(monster);
+        world.claim_ids();
+    }
+
+    #[test]
+    fn fast_monster_falls_into_a_chasm_once() {
+        let mut world = arena();
+        // a confused bat, acting twice a turn, ringed by chasm
+        for x in 9..=11 {
+            for y in 4..=6 {
+                world.game.map[x][y] = Tile::new(Terrain::Chasm);
+            }
+        }
+        world.game.map[10][5] = Tile::empty();
+        spawn(&mut world, "Giant bat", 10, 5);
+        spawn(&mut world, "Orc", 18, 1);
+        world.objects[1].add_status(StatusEffect {
+            kind: StatusKind::Confusion,
+            turns: 20,
+            potency: 0,
+        });
+        for _ in 0..5 {
+            world.step(Command::Wait);
+        }
+        let names: Vec<_> = world.objects.iter().map(|o| o.name.as_str()).collect();
+        assert_eq!(names, vec!["player", "Orc"]);
+    }
+
+    #[test]
+    fn fast_monster_drops_through_a_pit_once() {
+        let mut world = arena();
+        // the same confused bat, ringed by pit traps this time
+        for x in 9..=11 {
+            for y in 4..=6 {
+                if (x, y) != (10, 5) {
+                    world.objects.push(trap_object(TrapKind::Pit, x, y));
+                }
+            }
+        }
+        spawn(&mut world, "Giant bat", 10, 5);
+        spawn(&mut world, "Orc", 18, 1);
+        let bat = world.objects.len() - 2;
+        world.objects[bat].add_status(StatusEffect {
+            kind: StatusKind::Confusion,
+            turns: 20,
+            potency: 0,
+        });
+        for _ in 0..5 {
+            world.step(Command::Wait);
+        }
+        assert!(!world.objects.iter().any(|o| o.name == "Giant bat"));
+        assert_eq!(world.objects.iter().filter(|o| o.name == "Orc").count(), 1);
+        assert_eq!(world.objects.iter().filter(|o| o.trap.is_some()).count(), 8);
+    }
+
+    #[test]
+    fn combat_events_follow_objects_as_the_list_changes() {
+        let mut world = arena();
+        let potion = world.catalog.items.iter().find(|i| i.name == "Healing potion").unwrap();
+        world.objects.push(potion.spawn(5, 5));
+        spawn(&mut world, "Orc", 18, 1);
+        spawn(&mut world, "Orc", 6, 5);
+        world.objects[PLAYER].fighter.as_mut().unwrap().hp = 1000;
+        let player = world.objects[PLAYER].id;
+        let orc = world.objects[3].id;
+        // picking the potion up moves the orc next to the player in the list
+        world.step(Command::PickUp);
+        assert_eq!(world.objects[1].id, orc);
+
+        let mut events = vec![];
+        while !events.contains(&GameEvent::Died { id: orc }) && events.len() < 200 {
+            events.extend(world.step(Command::Move(1, 0)));
+        }
+        assert!(events.contains(&GameEvent::Died { id: orc }));
+        for event in events {
+            match event {
+                GameEvent::Attacked { attacker, target, .. }
+                | GameEvent::Missed { attacker, target } => {
+                    let pair = (attacker, target);
+                    assert!(pair == (player, orc) || pair == (orc, player));
+                }
+                GameEvent::Died { id } => assert_eq!(id, orc),
+                _ => {}
+            }
+        }
+        let last = world.game.log.iter().rev().find(|m| m.subject == Some(player)).unwrap();
+        assert_eq!(last.target, Some(orc));
+    }
+
+    #[test]
+    fn doors_out_of_reach_or_locked_take_no_turn() {
+        let mut world = arena();
+        world.game.inventory.retain(|item| item.item != Some(Item::Key));
+        world.game.map[6][5] = Tile::new(Terrain::LockedDoor);
+        world.game.map_changed = true;
+        let turn = world.game.turn;
+        world.step(Command::Door(500, 0));
+        world.step(Command::Door(-6, 0));
+        world.step(Command::Door(1, 0));
+        assert_eq!(world.game.turn, turn);
+        assert_eq!(world.game.map[6][5].terrain, Terrain::LockedDoor);
+    }
+
+    #[test]
+    fn magic_made_only_of_data_does_every_effect() {
+        let mut world = arena();
+        let scroll: ItemDef = serde_json::from_str(
+            r##"{
+                "name": "Scroll of stunning bolt",
+                "glyph": "#",
+                "color": { "r": 255, "g": 255, "b": 63 },
+                "effect": { "Magic": {
+                    "aim": { "Bolt": { "range": 6 } },
+                    "effects": [
+                        { "Damage": { "amount": 3 } },
+                        { "Status": { "status": "Confusion", "turns": 5, "potency": 0 } }
+                    ]
+                } },
+                "spawn": []
+            }"##,
+        )
+        .unwrap();
+        world.game.inventory.clear();
+        world.game.inventory.push(scroll.spawn(0, 0));
+        spawn(&mut world, "Orc", 8, 5);
+        world.step(Command::UseItem { inventory_id: 0, target: Some((8, 5)) });
+        let orc = &world.objects[1];
+        assert_eq!(orc.fighter.unwrap().hp, orc.max_hp(&world.game) - 3);
+        assert!(orc.has_status(StatusKind::Confusion));
+        assert!(world.game.inventory.is_empty());
+    }
+
+    /// Everything about a world that goes in a save, to compare runs by
+    fn snapshot(world: &World) -> String {
+        serde_json::to_string(&(&world.objects, &world.game)).unwrap()
+    }
+
+    /// What a player might do on a given turn: wander, wait, search and pick
+    /// things up
+    fn scripted(turn: usize) -> Command {
+        let moves = [(1, 0), (1, 1), (0, 1), (-1, 1), (-1, 0), (-1, -1), (0, -1), (1, -1)];
+        match turn % 10 {
+            0 => Command::Wait,
+            5 => Command::Search,
+            7 => Command::PickUp,
+            n => {
+                let (dx, dy) = moves[(turn / 4 + n) % moves.len()];
+                Command::Move(dx, dy)
+            }
+        }
+    }
+
+    /// A fresh world whose player lives long enough to see a few fights
+    fn sturdy(seed: u64) -> World {
+        let mut world = World::new(seed, Catalog::load().unwrap());
+        world.objects[PLAYER].fighter.as_mut().unwrap().hp = 10_000;
+        world
+    }
+
+    fn play(world: &mut World, turns: std::ops::Range<usize>) -> Vec<GameEvent> {
+        turns.flat_map(|turn| world.step(scripted(turn))).collect()
+    }
+
+    #[test]
+    fn same_seed_and_commands_give_the_same_run() {
+        let mut first = sturdy(42);
+        let mut second = sturdy(42);
+        assert_eq!(snapshot(&first), snapshot(&second));
+        let events = play(&mut first, 0..200);
+        assert_eq!(events, play(&mut second, 0..200));
+        assert_eq!(snapshot(&first), snapshot(&second));
+
+        let mut other = sturdy(43);
+        play(&mut other, 0..200);
+        assert_ne!(snapshot(&first), snapshot(&other));
+    }
+
+    /// A file of its own in the temp directory for a test to write to
+    fn scratch_file(name: &str) -> String {
+        let path = std::env::temp_dir().join(format!("ruguelike-{}-{}", name, std::process::id()));
+        path.to_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn replaying_the_recorded_commands_gives_the_same_run() {
+        let path = scratch_file("replay");
+        let mut world = World::new(42, Catalog::load().unwrap());
+        let mut recorder = Recorder::start_at(&path, &world).unwrap();
+        for turn in 0..200 {
+            let command = scripted(turn);
+            recorder.record(command).unwrap();
+            world.step(command);
+        }
+
+        let replay = read_replay(&path).unwrap();
+        std::fs::remove_file(&path).ok();
+        assert_eq!(replay.commands.len(), 200);
+        let mut replayed = World::new(replay.seed, Catalog::load().unwrap());
+        for &command in &replay.commands {
+            replayed.step(command);
+        }
+        assert_eq!(snapshot(&world), snapshot(&replayed));
+    }
+
+    /// Put the player on the stairs with this name and take them
+    fn take_stairs(world: &mut World, name: &str) -> Vec<GameEvent> {
+        let (x, y) = world.objects.iter().find(|o| o.name == name).unwrap().pos();
+        world.objects[PLAYER].set_pos(x, y);
+        world.step(Command::TakeStairs)
+    }
+
+    /// Down the stairs and back up again, then down once more, so both the
+    /// level left behind and the one being played go through the save
+    fn tour(world: &mut World, turns: std::ops::Range<usize>) -> Vec<GameEvent> {
+        let mut events = take_stairs(world, STAIRS_UP);
+        events.extend(play(world, turns.start..turns.start + 30));
+        events.extend(take_stairs(world, STAIRS_DOWN));
+        events.extend(play(world, turns.start + 30..turns.end));
+        events
+    }
+
+    #[test]
+    fn a_run_saved_and_loaded_halfway_goes_on_the_same() {
+        let path = scratch_file("savegame");
+        let mut kept = sturdy(42);
+        let mut saved = sturdy(42);
+        for world in [&mut kept, &mut saved].iter_mut() {
+            play(world, 0..60);
+            take_stairs(world, STAIRS_DOWN);
+            play(world, 60..100);
+        }
+        save_game_to(&path, &saved).unwrap();
+        let mut loaded = load_game_from(&path, Catalog::load().unwrap()).unwrap();
+        std::fs::remove_file(&path).ok();
+        assert_eq!(snapshot(&kept), snapshot(&loaded));
+
+        let events = tour(&mut kept, 100..200);
+        assert_eq!(events, tour(&mut loaded, 100..200));
+        assert_eq!(kept.game.dungeon_level, 2);
+        assert_eq!(snapshot(&kept), snapshot(&loaded));
+    }
+}
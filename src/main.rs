@@ -1,14 +1,25 @@
-#[macro_use]
-extern crate serde_derive;
-
 use tcod::console::*;
-use tcod::map::Map as FovMap;
-
-mod lib;
 
-use crate::lib::*;
+use ruguelike::*;
 
 fn main() {
+    // `--seed <n>` makes every new game start from the same dungeon
+    let mut seed = None;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--seed" {
+            seed = args.next().map(|s| s.parse::<u64>().expect("--seed expects a number"));
+        }
+    }
+
+    let catalog = match Catalog::load() {
+        Ok(catalog) => catalog,
+        Err(e) => {
+            eprintln!("Could not load the game data: {}", e);
+            std::process::exit(1);
+        }
+    };
+
     let root = Root::initializer()
         .font("arial10x10.png", FontLayout::Tcod)
         .font_type(FontType::Greyscale)
@@ -21,10 +32,8 @@ fn main() {
         root: root,
         con: Offscreen::new(MAP_WIDTH, MAP_HEIGHT),
         panel: Offscreen::new(SCREEN_WIDTH, PANEL_HEIGHT),
-        fov: FovMap::new(MAP_WIDTH, MAP_HEIGHT),
         mouse: Default::default(),
     };
 
-    main_menu(&mut tcod);
+    main_menu(&mut tcod, seed, &catalog);
 }
-